@@ -0,0 +1,18 @@
+use crate::{adapters::traits::Adapter, conversion::request::Request};
+
+// openrouter and litellm honor anthropic style cache_control on content parts for these models
+#[must_use]
+pub fn honors_cache_control(model: &str) -> bool {
+    let model = model.to_lowercase();
+    ["anthropic/", "claude", "gemini"]
+        .iter()
+        .any(|name| model.contains(name))
+}
+
+pub struct CacheControlAdapter(pub bool);
+
+impl Adapter for CacheControlAdapter {
+    fn adapt_forward_cache_control(&self, _forward: bool, _request: &Request) -> bool {
+        self.0
+    }
+}
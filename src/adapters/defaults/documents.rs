@@ -0,0 +1,28 @@
+use crate::{
+    adapters::traits::Adapter, conversion::request::Request, directives::models::DocumentMode,
+};
+
+// upstreams known to accept openai `file` content parts, the rest get the extracted text
+#[must_use]
+pub fn supports_file_parts(model: &str) -> bool {
+    let model = model.to_lowercase();
+    [
+        "openai/",
+        "gpt-4o",
+        "gpt-4.1",
+        "gpt-5",
+        "gemini",
+        "anthropic/",
+        "claude",
+    ]
+    .iter()
+    .any(|name| model.contains(name))
+}
+
+pub struct DocumentModeAdapter(pub DocumentMode);
+
+impl Adapter for DocumentModeAdapter {
+    fn adapt_document_mode(&self, _mode: DocumentMode, _request: &Request) -> DocumentMode {
+        self.0
+    }
+}
@@ -51,8 +51,7 @@ impl Adapter for ThreadOfMeowsingsAdapter {
             content: Some(crate::models::openai::OpenAIContent::Text(
                 seg_b().to_string(),
             )),
-            tool_calls: None,
-            tool_call_id: None,
+            ..Default::default()
         });
         new_messages
     }
@@ -0,0 +1,9 @@
+use crate::{adapters::traits::Adapter, conversion::request::Request};
+
+pub struct DeferMessageStartAdapter(pub bool);
+
+impl Adapter for DeferMessageStartAdapter {
+    fn adapt_defer_message_start(&self, _defer: bool, _request: &Request) -> bool {
+        self.0
+    }
+}
@@ -1,17 +1,29 @@
+mod caching;
+mod documents;
 mod gemini;
 mod meowsings;
+mod message_start;
+mod naming;
 mod parameters;
 mod prompt;
 mod responses_api;
+mod thinking;
+mod tool_results;
 pub mod tool_simulation;
 mod tools;
 
 pub use self::{
+    caching::{CacheControlAdapter, honors_cache_control},
+    documents::{DocumentModeAdapter, supports_file_parts},
     gemini::GeminiToolSchemaAdapter,
     meowsings::ThreadOfMeowsingsAdapter,
-    parameters::{KimiMaxTokensAdapter, OAIReasoningModelAdapter},
+    message_start::DeferMessageStartAdapter,
+    naming::{ToolNamingAdapter, requires_short_tool_ids},
+    parameters::{DropTopKAdapter, KimiMaxTokensAdapter, OAIReasoningModelAdapter, rejects_top_k},
     prompt::{DefaultSystemPromptAdapter, DefaultUserPromptAdapter},
     responses_api::ResponsesApiAdapter,
+    thinking::{ThinkingHistoryAdapter, replays_reasoning_content},
+    tool_results::ToolResultImagesAdapter,
     tool_simulation::{
         model::ToolSimulationModelAdapter, request::ToolSimulationRequestAdapter,
         response::ToolSimulationResponseAdapter, tools::ToolSimulationToolAdapter,
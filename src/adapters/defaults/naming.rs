@@ -0,0 +1,20 @@
+use crate::{
+    adapters::traits::Adapter, conversion::request::Request, directives::models::ToolNaming,
+};
+
+// mistral rejects any tool call id that isn't exactly 9 alphanumeric characters
+#[must_use]
+pub fn requires_short_tool_ids(model: &str) -> bool {
+    let model = model.to_lowercase();
+    ["mistral", "codestral", "devstral", "magistral"]
+        .iter()
+        .any(|name| model.contains(name))
+}
+
+pub struct ToolNamingAdapter(pub ToolNaming);
+
+impl Adapter for ToolNamingAdapter {
+    fn adapt_tool_naming(&self, _naming: ToolNaming, _request: &Request) -> ToolNaming {
+        self.0
+    }
+}
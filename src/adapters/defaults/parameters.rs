@@ -38,3 +38,20 @@ impl Adapter for OAIReasoningModelAdapter {
         }
     }
 }
+
+// openai models reject top_k as an unknown parameter on both chat completions and responses
+#[must_use]
+pub fn rejects_top_k(model: &str) -> bool {
+    let model = model.to_lowercase();
+    ["openai/", "gpt-", "o1", "o3", "o4-mini"]
+        .iter()
+        .any(|name| model.starts_with(name) || model.contains(&format!("/{name}")))
+}
+
+pub struct DropTopKAdapter;
+
+impl Adapter for DropTopKAdapter {
+    fn adapt_top_k(&self, _top_k: Option<u32>, _request: &Request) -> Option<u32> {
+        None
+    }
+}
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, pin::Pin};
 
 use async_stream::stream;
+use axum::http::StatusCode;
 use futures_util::{Stream, StreamExt, TryStreamExt};
 use serde_json::{Map, Value, json};
 use tokio_sse_codec::{Frame, SseDecoder};
@@ -10,11 +11,18 @@ use tracing::debug;
 
 use crate::{
     adapters::traits::ApiAdapter,
-    conversion::request::Request,
-    error::AppError,
-    models::openai::{
-        OpenAIContent, OpenAIDelta, OpenAIRequest, OpenAIStreamChoice, OpenAIStreamChunk,
-        OpenAIStreamFunction, OpenAIStreamToolCall, OpenAIUsage,
+    conversion::{
+        request::Request,
+        server_tools::{responses_web_search, server_tools},
+    },
+    error::{AppError, UpstreamFailure},
+    models::{
+        claude::ClaudeServerTool,
+        openai::{
+            OpenAIContent, OpenAIDelta, OpenAIRequest, OpenAIStreamChoice, OpenAIStreamChunk,
+            OpenAIStreamFunction, OpenAIStreamToolCall, OpenAIUsage,
+        },
+        shared::PromptTokensDetails,
     },
 };
 
@@ -24,6 +32,25 @@ pub struct ResponsesApiAdapter {
 }
 
 impl ResponsesApiAdapter {
+    fn tools(openai_req: &OpenAIRequest, original: &Request) -> Vec<Value> {
+        let functions = openai_req.tools.iter().flatten().filter_map(|t| {
+            if t.tool_type == "function" {
+                Some(json!({
+                    "type": "function",
+                    "name": t.function.name,
+                    "parameters": t.function.parameters,
+                    "strict": false,
+                    "description": t.function.description,
+                }))
+            } else {
+                None
+            }
+        });
+        functions
+            .chain(server_tools(original).filter_map(responses_web_search))
+            .collect()
+    }
+
     fn map_messages_to_input(messages: &[crate::models::openai::OpenAIMessage]) -> Value {
         let mut items: Vec<Value> = Vec::new();
         for message in messages {
@@ -43,7 +70,13 @@ impl ResponsesApiAdapter {
 
     fn map_tool_output_item(message: &crate::models::openai::OpenAIMessage) -> Value {
         let call_id = message.tool_call_id.clone().unwrap_or_default();
-        let output = Self::extract_tool_output_string(message.content.as_ref());
+        let output = match message.content.as_ref() {
+            // images and files returned by the tool stay in the output as input_* items
+            Some(OpenAIContent::Array(parts)) if parts.iter().any(|part| part.text.is_none()) => {
+                Value::Array(Self::map_array_parts_for_role("user", parts))
+            }
+            content => Value::String(Self::extract_tool_output_string(content)),
+        };
         json!({
             "type": "function_call_output",
             "call_id": call_id,
@@ -133,6 +166,17 @@ impl ResponsesApiAdapter {
                             })
                         })
                     }
+                } else if part.part_type == "file" && role != "assistant" {
+                    part.file.as_ref().map(|file| {
+                        let mut item = json!({
+                            "type": "input_file",
+                            "file_data": file.file_data
+                        });
+                        if let Some(filename) = &file.filename {
+                            item["filename"] = json!(filename);
+                        }
+                        item
+                    })
                 } else {
                     None
                 }
@@ -210,6 +254,7 @@ impl ResponsesApiAdapter {
                     ..Default::default()
                 },
                 finish_reason: None,
+                stop_reason: None,
             }],
             model: model.to_string(),
             usage: OpenAIUsage::default(),
@@ -231,6 +276,7 @@ impl ResponsesApiAdapter {
                     ..Default::default()
                 },
                 finish_reason: None,
+                stop_reason: None,
             }],
             model: model.to_string(),
             usage: OpenAIUsage::default(),
@@ -249,6 +295,7 @@ impl ResponsesApiAdapter {
                     ..Default::default()
                 },
                 finish_reason: None,
+                stop_reason: None,
             }],
             model: model.to_string(),
             usage: OpenAIUsage::default(),
@@ -299,6 +346,7 @@ impl ResponsesApiAdapter {
                     ..Default::default()
                 },
                 finish_reason: None,
+                stop_reason: None,
             }],
             model: model.to_string(),
             usage: OpenAIUsage::default(),
@@ -318,6 +366,7 @@ impl ResponsesApiAdapter {
                 index: 0,
                 delta: OpenAIDelta::default(),
                 finish_reason: Some("tool_calls".to_string()),
+                stop_reason: None,
             }],
             model: model.to_string(),
             usage: OpenAIUsage::default(),
@@ -325,12 +374,55 @@ impl ResponsesApiAdapter {
         Some(chunk)
     }
 
+    fn instructions(messages: &[crate::models::openai::OpenAIMessage]) -> String {
+        messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .filter_map(|m| match &m.content {
+                Some(OpenAIContent::Text(t)) => Some(t.clone()),
+                // a system prompt split at cache breakpoints
+                Some(OpenAIContent::Array(parts)) => Some(
+                    parts
+                        .iter()
+                        .filter_map(|part| part.text.as_deref())
+                        .collect::<String>(),
+                ),
+                None => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn prompt_tokens_details(usage: &Value) -> Option<PromptTokensDetails> {
+        usage
+            .pointer("/input_tokens_details/cached_tokens")
+            .and_then(Value::as_u64)
+            .and_then(|cached| u32::try_from(cached).ok())
+            .map(|cached| PromptTokensDetails {
+                cached_tokens: Some(cached),
+                cache_write_tokens: None,
+            })
+    }
+
+    // an incomplete response stopped at max_output_tokens or a content filter
+    fn finish_reason(response: &Value) -> &'static str {
+        match response
+            .pointer("/incomplete_details/reason")
+            .and_then(Value::as_str)
+        {
+            Some("content_filter") => "content_filter",
+            Some(_) => "length",
+            None if response.get("status").and_then(Value::as_str) == Some("incomplete") => {
+                "length"
+            }
+            None => "stop",
+        }
+    }
+
+    // response.incomplete carries the same payload as response.completed
     fn handle_completed(parsed: &Value, model: &str) -> OpenAIStreamChunk {
-        let usage = parsed
-            .get("response")
-            .and_then(|r| r.get("usage"))
-            .cloned()
-            .unwrap_or(json!({}));
+        let response = parsed.get("response").cloned().unwrap_or(json!({}));
+        let usage = response.get("usage").cloned().unwrap_or(json!({}));
         let prompt_tokens = u32::try_from(
             usage
                 .get("input_tokens")
@@ -350,13 +442,15 @@ impl ResponsesApiAdapter {
             choices: vec![OpenAIStreamChoice {
                 index: 0,
                 delta: OpenAIDelta::default(),
-                finish_reason: Some("stop".to_string()),
+                finish_reason: Some(Self::finish_reason(&response).to_string()),
+                stop_reason: None,
             }],
             model: model.to_string(),
             usage: OpenAIUsage {
                 prompt_tokens,
                 completion_tokens,
-                prompt_tokens_details: None,
+                prompt_tokens_details: Self::prompt_tokens_details(&usage),
+                ..OpenAIUsage::default()
             },
         }
     }
@@ -367,18 +461,12 @@ impl ApiAdapter for ResponsesApiAdapter {
         "/responses"
     }
 
-    fn build_body(&self, openai_req: &OpenAIRequest, _original: &Request) -> Value {
-        let instructions = openai_req
-            .messages
-            .iter()
-            .filter(|m| m.role == "system")
-            .filter_map(|m| match &m.content {
-                Some(OpenAIContent::Text(t)) => Some(t.clone()),
-                _ => None,
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+    fn supports_server_tool(&self, tool: &ClaudeServerTool) -> bool {
+        responses_web_search(tool).is_some()
+    }
 
+    fn build_body(&self, openai_req: &OpenAIRequest, original: &Request) -> Value {
+        let instructions = Self::instructions(&openai_req.messages);
         let input = Self::map_messages_to_input(&openai_req.messages);
 
         let mut body = Map::new();
@@ -417,26 +505,9 @@ impl ApiAdapter for ResponsesApiAdapter {
         if let Some(max_out) = self.max_output_tokens {
             body.insert("max_output_tokens".to_string(), json!(max_out));
         }
-        if let Some(tools) = &openai_req.tools {
-            let mapped_tools: Vec<Value> = tools
-                .iter()
-                .filter_map(|t| {
-                    if t.tool_type == "function" {
-                        Some(json!({
-                            "type": "function",
-                            "name": t.function.name,
-                            "parameters": t.function.parameters,
-                            "strict": false,
-                            "description": t.function.description,
-                        }))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            if !mapped_tools.is_empty() {
-                body.insert("tools".to_string(), Value::Array(mapped_tools));
-            }
+        let mapped_tools = Self::tools(openai_req, original);
+        if !mapped_tools.is_empty() {
+            body.insert("tools".to_string(), Value::Array(mapped_tools));
         }
         if let Some(choice) = &openai_req.tool_choice {
             let mapped_choice = match choice {
@@ -454,6 +525,18 @@ impl ApiAdapter for ResponsesApiAdapter {
             };
             body.insert("tool_choice".to_string(), mapped_choice);
         }
+        if let Some(user) = &openai_req.user {
+            body.insert("user".to_string(), json!(user));
+        }
+        if let Some(service_tier) = &openai_req.service_tier {
+            body.insert("service_tier".to_string(), json!(service_tier));
+        }
+        if let Some(parallel_tool_calls) = openai_req.parallel_tool_calls {
+            body.insert(
+                "parallel_tool_calls".to_string(),
+                json!(parallel_tool_calls),
+            );
+        }
         Value::Object(body)
     }
 
@@ -505,15 +588,7 @@ impl ApiAdapter for ResponsesApiAdapter {
                 }));
             }
         }
-        let status = upstream_json
-            .get("status")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        let finish_reason = if status == "incomplete" {
-            "length"
-        } else {
-            "stop"
-        };
+        let finish_reason = Self::finish_reason(&upstream_json);
         let usage = upstream_json
             .get("usage")
             .cloned()
@@ -546,7 +621,8 @@ impl ApiAdapter for ResponsesApiAdapter {
             ],
             "usage": {
                 "prompt_tokens": prompt_tokens,
-                "completion_tokens": completion_tokens
+                "completion_tokens": completion_tokens,
+                "prompt_tokens_details": Self::prompt_tokens_details(&usage),
             }
         })
     }
@@ -585,7 +661,15 @@ impl ApiAdapter for ResponsesApiAdapter {
                             "response.output_text.delta" => Self::handle_output_text_delta(&parsed, &model),
                             "response.function_call_arguments.delta" => Self::handle_function_call_arguments_delta(&parsed, &mut call_index_map, &mut next_index, &model),
                             "response.output_item.done" => Self::handle_output_item_done(&parsed, &model),
-                            "response.completed" => {
+                            "error" | "response.failed" => {
+                                let payload = parsed.get("response").cloned().unwrap_or_else(|| json!({"error": parsed}));
+                                let failure = UpstreamFailure::from_stream_payload(&payload).unwrap_or_else(|| {
+                                    UpstreamFailure::new(StatusCode::OK, data, None)
+                                });
+                                yield Err(AppError::UpstreamError(failure));
+                                break;
+                            }
+                            "response.completed" | "response.incomplete" => {
                                 let chunk = Self::handle_completed(&parsed, &model);
                                 yield Ok(chunk);
                                 break;
@@ -0,0 +1,32 @@
+use crate::{
+    adapters::traits::Adapter, conversion::request::Request, directives::models::ThinkingHistory,
+};
+
+// models with interleaved reasoning lose their chain of thought between tool calls without this
+#[must_use]
+pub fn replays_reasoning_content(model: &str) -> bool {
+    let model = model.to_lowercase();
+    [
+        "kimi-k2-thinking",
+        "glm-4.5",
+        "glm-4p5",
+        "glm-4.6",
+        "glm-4p6",
+        "deepseek",
+        "minimax",
+    ]
+    .iter()
+    .any(|name| model.contains(name))
+}
+
+pub struct ThinkingHistoryAdapter(pub ThinkingHistory);
+
+impl Adapter for ThinkingHistoryAdapter {
+    fn adapt_thinking_history(
+        &self,
+        _policy: ThinkingHistory,
+        _request: &Request,
+    ) -> ThinkingHistory {
+        self.0
+    }
+}
@@ -0,0 +1,16 @@
+use crate::{
+    adapters::traits::Adapter,
+    conversion::request::{Request, ToolResultImages},
+};
+
+pub struct ToolResultImagesAdapter(pub ToolResultImages);
+
+impl Adapter for ToolResultImagesAdapter {
+    fn adapt_tool_result_images(
+        &self,
+        _placement: ToolResultImages,
+        _request: &Request,
+    ) -> ToolResultImages {
+        self.0
+    }
+}
@@ -4,3 +4,5 @@ pub mod request;
 pub mod response;
 pub mod streaming;
 pub mod tools;
+
+pub const TOOL_SIMULATION_SUFFIXES: [&str; 2] = ["-xml-tools", "-bracket-tools"];
@@ -1,4 +1,5 @@
 use super::streaming::{BracketGrammar, ToolGrammar, XmlGrammar};
+use crate::conversion::tool_mapping::generate_tool_use_id;
 use chumsky::prelude::*;
 use llm_json::{RepairOptions, loads};
 use memchr::{memchr, memmem::Finder};
@@ -309,7 +310,7 @@ pub fn build_non_stream_response(parsed: ParsedToolResponse) -> Value {
                     .into_iter()
                     .map(|tool_call| {
                         json!({
-                            "id": format!("call_{}", rand::random::<u32>()),
+                            "id": generate_tool_use_id(),
                             "type": "function",
                             "function": {
                                 "name": tool_call.name,
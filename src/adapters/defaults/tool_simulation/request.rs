@@ -1,6 +1,6 @@
 use crate::{
     adapters::traits::Adapter,
-    conversion::request::Request,
+    conversion::{request::Request, server_tools::function_tools},
     models::{
         claude::ClaudeTool,
         openai::{OpenAIContent, OpenAIMessage},
@@ -232,9 +232,19 @@ impl ToolSimulationRequestAdapter {
     }
 
     fn convert_tool_message(message: OpenAIMessage, request: &Request) -> OpenAIMessage {
-        let content = if let (Some(id), Some(OpenAIContent::Text(text))) =
-            (message.tool_call_id, message.content)
-        {
+        // array content comes from cache breakpoints or tool images, only its text can be simulated
+        let text = match message.content {
+            Some(OpenAIContent::Text(text)) => Some(text),
+            Some(OpenAIContent::Array(parts)) => Some(
+                parts
+                    .into_iter()
+                    .filter_map(|part| part.text)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            None => None,
+        };
+        let content = if let (Some(id), Some(text)) = (message.tool_call_id, text) {
             let name = request.find_tool_name_by_id(&id).unwrap_or_default();
             if request.model.ends_with("-xml-tools") {
                 format!(
@@ -295,7 +305,7 @@ fn generate_system_prompt(
     separator: &str,
     tool_formatter: impl Fn(&ClaudeTool) -> String,
 ) -> String {
-    let tools = match &request.tools {
+    let tools = match function_tools(request) {
         Some(tools) if !tools.is_empty() => tools,
         _ => return system_prompt.to_string(),
     };
@@ -4,7 +4,7 @@ use super::{
 };
 use crate::{
     adapters::traits::Adapter,
-    conversion::request::Request,
+    conversion::{request::Request, tool_mapping::generate_tool_use_id},
     error::AppError,
     models::openai::{
         OpenAIDelta, OpenAIStreamChoice, OpenAIStreamChunk, OpenAIStreamFunction,
@@ -106,6 +106,7 @@ impl Adapter for ToolSimulationResponseAdapter {
                             index: 0,
                             delta: OpenAIDelta::default(),
                             finish_reason: Some("tool_calls".to_string()),
+                            stop_reason: None,
                         }];
                         yield Ok(finish_chunk);
                     }
@@ -183,9 +184,7 @@ fn tool_event_to_choice(
 ) -> Option<OpenAIStreamChoice> {
     match event {
         ToolEvent::Start { index, name } => {
-            let id = call_ids
-                .entry(index)
-                .or_insert_with(|| format!("call_{}", rand::random::<u32>()));
+            let id = call_ids.entry(index).or_insert_with(generate_tool_use_id);
             Some(OpenAIStreamChoice {
                 index: 0,
                 delta: OpenAIDelta {
@@ -201,12 +200,11 @@ fn tool_event_to_choice(
                     ..Default::default()
                 },
                 finish_reason: None,
+                stop_reason: None,
             })
         }
         ToolEvent::Arg { index, delta } => {
-            let id = call_ids
-                .entry(index)
-                .or_insert_with(|| format!("call_{}", rand::random::<u32>()));
+            let id = call_ids.entry(index).or_insert_with(generate_tool_use_id);
             Some(OpenAIStreamChoice {
                 index: 0,
                 delta: OpenAIDelta {
@@ -222,6 +220,7 @@ fn tool_event_to_choice(
                     ..Default::default()
                 },
                 finish_reason: None,
+                stop_reason: None,
             })
         }
         ToolEvent::End | ToolEvent::ToolsBlockEnd => None,
@@ -1,4 +1,5 @@
 use super::parsing::ParsedToolCall;
+use crate::telemetry;
 use memchr::memmem;
 use tokio::task::consume_budget;
 use tracing::debug;
@@ -774,6 +775,7 @@ impl StreamingToolParser {
     pub async fn finalize(&mut self) -> Vec<ToolEvent> {
         consume_budget().await;
         let mut events = Vec::new();
+        let format = if self.is_xml { "xml" } else { "bracket" };
         if matches!(self.state, ParserState::InToolBlock { .. }) {
             let mut buffer = match std::mem::replace(&mut self.state, ParserState::Passthrough) {
                 ParserState::InToolBlock { buffer } => buffer,
@@ -798,6 +800,14 @@ impl StreamingToolParser {
                 events.push(ToolEvent::End);
             }
             self.state = ParserState::InToolBlock { buffer };
+            let outcome = if events.is_empty() {
+                "unterminated"
+            } else {
+                "recovered"
+            };
+            telemetry::record_tool_simulation_finalize(format, outcome);
+        } else {
+            telemetry::record_tool_simulation_finalize(format, "clean");
         }
         events
     }
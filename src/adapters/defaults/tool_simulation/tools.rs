@@ -1,6 +1,7 @@
 use crate::{
     adapters::traits::Adapter,
     conversion::request::Request,
+    directives::models::ToolNaming,
     models::claude::{ClaudeTool, ClaudeToolChoice},
 };
 
@@ -22,4 +23,9 @@ impl Adapter for ToolSimulationToolAdapter {
     ) -> Option<ClaudeToolChoice> {
         None
     }
+
+    // simulated calls are written into the prompt with the names and ids the client knows
+    fn adapt_tool_naming(&self, _naming: ToolNaming, _request: &Request) -> ToolNaming {
+        ToolNaming::Verbatim
+    }
 }
@@ -1,76 +1,124 @@
 pub mod defaults;
 pub mod traits;
 
-use std::{env, pin::Pin, sync::Arc};
+use std::{pin::Pin, sync::Arc};
 
 use crate::{
-    conversion::request::Request,
-    conversion::stream::{chunks_to_events, emit_event, emit_ping},
-    directives::models::Settings,
+    config::Config,
+    conversion::request::{Request, ToolResultImages},
+    conversion::server_tools::{function_equivalent, server_tools},
+    conversion::stream::{StreamOptions, parse_openai_sse_chunks},
+    directives::models::{DocumentMode, Settings, ThinkingHistory, ToolNaming},
     error::AppError,
     models::{
         claude::ClaudeMessagesRequest, openai::OpenAIMessage, openai::OpenAIRequest,
         openai::OpenAIStreamChunk,
     },
+    retry::ChunkStream,
     state::AppState,
 };
 use bytes::Bytes;
-use futures_util::{Stream, StreamExt};
+use futures_util::Stream;
 use serde_json::{Value, json};
 
 use self::{
     defaults::{
-        DefaultSystemPromptAdapter, DefaultToolsAdapter, DefaultUserPromptAdapter,
+        CacheControlAdapter, DefaultSystemPromptAdapter, DefaultToolsAdapter,
+        DefaultUserPromptAdapter, DeferMessageStartAdapter, DocumentModeAdapter, DropTopKAdapter,
         GeminiToolSchemaAdapter, KimiMaxTokensAdapter, OAIReasoningModelAdapter,
-        ResponsesApiAdapter, ThreadOfMeowsingsAdapter, ToolSimulationModelAdapter,
-        ToolSimulationRequestAdapter, ToolSimulationResponseAdapter, ToolSimulationToolAdapter,
+        ResponsesApiAdapter, ThinkingHistoryAdapter, ThreadOfMeowsingsAdapter, ToolNamingAdapter,
+        ToolResultImagesAdapter, ToolSimulationModelAdapter, ToolSimulationRequestAdapter,
+        ToolSimulationResponseAdapter, ToolSimulationToolAdapter, honors_cache_control,
+        rejects_top_k, replays_reasoning_content, requires_short_tool_ids, supports_file_parts,
     },
     traits::{Adapter, ApiAdapter},
 };
 
+#[derive(Clone)]
 pub struct RequestAdapter {
     adapters: Vec<Arc<dyn Adapter>>,
     api: Option<Arc<dyn ApiAdapter>>,
+    profile: Vec<&'static str>,
 }
 
 impl RequestAdapter {
     #[must_use]
-    pub fn for_model(model: &str, settings: &Settings) -> Self {
+    pub fn for_model(model: &str, settings: &Settings, config: &Config) -> Self {
         let mut adapters: Vec<Arc<dyn Adapter>> = Vec::new();
+        let mut profile = Vec::new();
 
-        let disable_defaults =
-            env::var("DISABLE_DEFAULT_ADAPTERS").is_ok_and(|value| value == "1" || value == "true");
-
-        if !disable_defaults {
+        if !config.disable_default_adapters {
+            profile.push("defaults");
             adapters.push(Arc::new(DefaultSystemPromptAdapter));
             adapters.push(Arc::new(DefaultUserPromptAdapter));
             adapters.push(Arc::new(DefaultToolsAdapter));
 
             let is_gemini = model.contains("gemini");
-            let is_kimi = model.contains("moonshotai/kimi-k2-instruct")
-                && !env::var("DISABLE_GROQ_MAX_TOKENS")
-                    .is_ok_and(|value| value == "1" || value == "true");
+            let is_kimi =
+                model.contains("moonshotai/kimi-k2-instruct") && !config.disable_groq_max_tokens;
 
             if is_gemini {
+                profile.push("gemini");
                 adapters.push(Arc::new(GeminiToolSchemaAdapter));
             }
             if is_kimi {
+                profile.push("kimi");
                 adapters.push(Arc::new(KimiMaxTokensAdapter));
             }
             adapters.push(Arc::new(OAIReasoningModelAdapter));
+            if rejects_top_k(model) {
+                adapters.push(Arc::new(DropTopKAdapter));
+            }
+            if replays_reasoning_content(model) {
+                adapters.push(Arc::new(ThinkingHistoryAdapter(
+                    ThinkingHistory::ReasoningContent,
+                )));
+            }
+            if supports_file_parts(model) {
+                adapters.push(Arc::new(DocumentModeAdapter(DocumentMode::File)));
+            }
+            if honors_cache_control(model) {
+                adapters.push(Arc::new(CacheControlAdapter(true)));
+            }
+            if requires_short_tool_ids(model) {
+                adapters.push(Arc::new(ToolNamingAdapter(ToolNaming::Mistral)));
+            }
+        }
+
+        if config.defer_message_start {
+            adapters.push(Arc::new(DeferMessageStartAdapter(true)));
         }
 
         if settings.enable_meowsings.unwrap_or(false) {
+            profile.push("meowsings");
             adapters.push(Arc::new(ThreadOfMeowsingsAdapter));
         }
 
         if model.ends_with("-xml-tools") || model.ends_with("-bracket-tools") {
+            profile.push("tool_simulation");
             adapters.push(Arc::new(ToolSimulationRequestAdapter));
             adapters.push(Arc::new(ToolSimulationResponseAdapter));
             adapters.push(Arc::new(ToolSimulationModelAdapter));
             adapters.push(Arc::new(ToolSimulationToolAdapter));
         }
 
+        // directives win over the per-model default above
+        if let Some(policy) = settings.thinking_history {
+            adapters.push(Arc::new(ThinkingHistoryAdapter(policy)));
+        }
+        if let Some(mode) = settings.documents {
+            adapters.push(Arc::new(DocumentModeAdapter(mode)));
+        }
+        if let Some(forward) = settings.forward_cache_control {
+            adapters.push(Arc::new(CacheControlAdapter(forward)));
+        }
+        if let Some(naming) = settings.tool_naming {
+            adapters.push(Arc::new(ToolNamingAdapter(naming)));
+        }
+        if let Some(defer) = settings.defer_message_start {
+            adapters.push(Arc::new(DeferMessageStartAdapter(defer)));
+        }
+
         let api = match settings.responses.as_ref() {
             Some(responses_settings) if responses_settings.enable.unwrap_or(false) => {
                 Some(Arc::new(ResponsesApiAdapter {
@@ -80,8 +128,30 @@ impl RequestAdapter {
             }
             _ => None,
         };
+        if api.is_some() {
+            profile.push("responses");
+            // the responses api has no top_k parameter whatever the model
+            adapters.push(Arc::new(DropTopKAdapter));
+            adapters.push(Arc::new(ToolResultImagesAdapter(
+                ToolResultImages::ToolContent,
+            )));
+        }
+
+        Self {
+            adapters,
+            api,
+            profile,
+        }
+    }
 
-        Self { adapters, api }
+    // low cardinality summary of the adapter chain, used as a metrics label
+    #[must_use]
+    pub fn adapter_set(&self) -> String {
+        if self.profile.is_empty() {
+            "none".to_string()
+        } else {
+            self.profile.join("+")
+        }
     }
 
     #[must_use]
@@ -108,6 +178,21 @@ impl RequestAdapter {
         }
     }
 
+    // server tools that have neither a function form nor support in the upstream api
+    #[must_use]
+    pub fn dropped_server_tools(&self, request: &Request) -> Vec<String> {
+        server_tools(request)
+            .filter(|tool| function_equivalent(tool).is_none())
+            .filter(|tool| {
+                !self
+                    .api
+                    .as_ref()
+                    .is_some_and(|api| api.supports_server_tool(tool))
+            })
+            .map(|tool| tool.name.clone())
+            .collect()
+    }
+
     #[must_use]
     pub fn normalize_non_stream_json(&self, response_json: Value, _original: &Request) -> Value {
         match self.api.as_ref() {
@@ -116,6 +201,15 @@ impl RequestAdapter {
         }
     }
 
+    #[must_use]
+    pub fn chunk_stream(&self, response: reqwest::Response, original: &Request) -> ChunkStream {
+        let chunk_stream = match self.api.as_ref() {
+            Some(api) => api.chunk_stream(response, original),
+            None => parse_openai_sse_chunks(response),
+        };
+        self.adapt_chunk_stream(chunk_stream, original)
+    }
+
     #[must_use]
     pub fn build_anthropic_sse_stream(
         &self,
@@ -123,53 +217,16 @@ impl RequestAdapter {
         target_model: &str,
         original: &ClaudeMessagesRequest,
         state: &AppState,
+        options: StreamOptions,
     ) -> Pin<Box<dyn Stream<Item = Result<Bytes, AppError>> + Send>> {
-        match self.api.as_ref() {
-            Some(api) => {
-                let chunk_stream = api.chunk_stream(response, original);
-                let adapted_chunks = self.adapt_chunk_stream(Box::pin(chunk_stream), original);
-                let event_stream =
-                    chunks_to_events(target_model, adapted_chunks, state.idle_connection_timeout);
-                Box::pin(async_stream::stream! {
-                    let mut stream = Box::pin(event_stream);
-                    let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
-                    loop {
-                        tokio::select! {
-                            event_result = stream.next() => {
-                                if let Some(event_result) = event_result {
-                                    match event_result {
-                                        Ok(event) => {
-                                            let (event_type, data) = event.to_parts();
-                                            yield Ok(emit_event(event_type, &data));
-                                        }
-                                        Err(e) => {
-                                            let error_event = json!({
-                                                "type": "error",
-                                                "error": { "type": "api_error", "message": e.to_string() }
-                                            });
-                                            yield Ok(emit_event("error", &error_event));
-                                            break;
-                                        }
-                                    }
-                                } else {
-                                    break;
-                                }
-                            }
-                            _ = ping_interval.tick() => {
-                                yield Ok(emit_ping());
-                            }
-                        }
-                    }
-                })
-            }
-            None => crate::conversion::convert_openai_stream_to_anthropic(
-                response,
-                target_model,
-                self,
-                original,
-                state,
-            ),
-        }
+        crate::conversion::convert_openai_stream_to_anthropic(
+            response,
+            target_model,
+            self,
+            original,
+            state,
+            options,
+        )
     }
 
     #[must_use]
@@ -201,6 +258,47 @@ impl RequestAdapter {
             })
     }
 
+    #[must_use]
+    pub fn thinking_history(&self, request: &Request) -> ThinkingHistory {
+        self.adapters
+            .iter()
+            .fold(ThinkingHistory::default(), |policy, adapter| {
+                adapter.adapt_thinking_history(policy, request)
+            })
+    }
+
+    #[must_use]
+    pub fn document_mode(&self, request: &Request) -> DocumentMode {
+        self.adapters
+            .iter()
+            .fold(DocumentMode::default(), |mode, adapter| {
+                adapter.adapt_document_mode(mode, request)
+            })
+    }
+
+    #[must_use]
+    pub fn forwards_cache_control(&self, request: &Request) -> bool {
+        self.adapters.iter().fold(false, |forward, adapter| {
+            adapter.adapt_forward_cache_control(forward, request)
+        })
+    }
+
+    #[must_use]
+    pub fn tool_naming(&self, request: &Request) -> ToolNaming {
+        self.adapters
+            .iter()
+            .fold(ToolNaming::default(), |naming, adapter| {
+                adapter.adapt_tool_naming(naming, request)
+            })
+    }
+
+    #[must_use]
+    pub fn defers_message_start(&self, request: &Request) -> bool {
+        self.adapters.iter().fold(false, |defer, adapter| {
+            adapter.adapt_defer_message_start(defer, request)
+        })
+    }
+
     #[must_use]
     pub fn adapt_tools(
         &self,
@@ -241,6 +339,13 @@ impl RequestAdapter {
             .fold(top_p, |top_p, adapter| adapter.adapt_top_p(top_p, request))
     }
 
+    #[must_use]
+    pub fn adapt_top_k(&self, top_k: Option<u32>, request: &Request) -> Option<u32> {
+        self.adapters
+            .iter()
+            .fold(top_k, |top_k, adapter| adapter.adapt_top_k(top_k, request))
+    }
+
     #[must_use]
     pub fn adapt_max_tokens(&self, max_tokens: u32, request: &Request) -> Option<u32> {
         self.adapters
@@ -271,6 +376,15 @@ impl RequestAdapter {
             })
     }
 
+    #[must_use]
+    pub fn tool_result_images(&self, request: &Request) -> ToolResultImages {
+        self.adapters
+            .iter()
+            .fold(ToolResultImages::default(), |placement, adapter| {
+                adapter.adapt_tool_result_images(placement, request)
+            })
+    }
+
     #[must_use]
     pub fn adapt_tool_schema(
         &self,
@@ -1,8 +1,9 @@
 use crate::{
-    conversion::request::Request,
+    conversion::request::{Request, ToolResultImages},
+    directives::models::{DocumentMode, ThinkingHistory, ToolNaming},
     error::AppError,
     models::{
-        claude::{ClaudeTool, ClaudeToolChoice},
+        claude::{ClaudeServerTool, ClaudeTool, ClaudeToolChoice},
         openai::{OpenAIMessage, OpenAIRequest, OpenAIStreamChunk},
     },
 };
@@ -51,6 +52,10 @@ pub trait Adapter: Send + Sync {
         top_p
     }
 
+    fn adapt_top_k(&self, top_k: Option<u32>, _request: &Request) -> Option<u32> {
+        top_k
+    }
+
     fn adapt_max_tokens(&self, max_tokens: u32, _request: &Request) -> Option<u32> {
         Some(max_tokens)
     }
@@ -67,10 +72,42 @@ pub trait Adapter: Send + Sync {
         tool_result.to_string()
     }
 
+    fn adapt_tool_result_images(
+        &self,
+        placement: ToolResultImages,
+        _request: &Request,
+    ) -> ToolResultImages {
+        placement
+    }
+
     fn adapt_model(&self, model: &str, _request: &Request) -> String {
         model.to_string()
     }
 
+    fn adapt_thinking_history(
+        &self,
+        policy: ThinkingHistory,
+        _request: &Request,
+    ) -> ThinkingHistory {
+        policy
+    }
+
+    fn adapt_document_mode(&self, mode: DocumentMode, _request: &Request) -> DocumentMode {
+        mode
+    }
+
+    fn adapt_forward_cache_control(&self, forward: bool, _request: &Request) -> bool {
+        forward
+    }
+
+    fn adapt_tool_naming(&self, naming: ToolNaming, _request: &Request) -> ToolNaming {
+        naming
+    }
+
+    fn adapt_defer_message_start(&self, defer: bool, _request: &Request) -> bool {
+        defer
+    }
+
     fn adapt_messages(
         &self,
         messages: Vec<OpenAIMessage>,
@@ -96,6 +133,9 @@ pub trait ApiAdapter: Send + Sync {
     fn endpoint_suffix(&self) -> &'static str;
     fn build_body(&self, openai_req: &OpenAIRequest, original: &Request) -> Value;
     fn normalize_non_stream_json(&self, upstream_json: Value) -> Value;
+    fn supports_server_tool(&self, _tool: &ClaudeServerTool) -> bool {
+        false
+    }
     fn chunk_stream(
         &self,
         response: reqwest::Response,
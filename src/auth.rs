@@ -0,0 +1,47 @@
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ApiKeyQuery {
+    #[serde(rename = "api-key")]
+    pub api_key: Option<String>,
+}
+
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = header_value(headers, AUTHORIZATION.as_str())?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+// precedence: x-api-key, then Authorization: Bearer, then the api-key query parameter.
+// the prefix lets operators namespace ant-compat keys, it is only stripped when present
+#[must_use]
+pub fn extract_api_key(
+    headers: &HeaderMap,
+    query: &ApiKeyQuery,
+    prefix: Option<&str>,
+) -> Option<String> {
+    let key = header_value(headers, "x-api-key")
+        .or_else(|| bearer_token(headers))
+        .or_else(|| {
+            query
+                .api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+        })?;
+    let key = prefix
+        .filter(|prefix| !prefix.is_empty())
+        .and_then(|prefix| key.strip_prefix(prefix))
+        .unwrap_or(key);
+    Some(key.to_string())
+}
@@ -0,0 +1,382 @@
+use std::{fmt::Display, fs, path::PathBuf, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::retry::RetryPolicy;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to parse config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid value {value:?} for {name}: {reason}")]
+    InvalidEnv {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+    #[error("Invalid config: {0}")]
+    Invalid(String),
+    #[error("Invalid arguments: {0}")]
+    Args(String),
+    #[error("Failed to serialize config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadinessProbe {
+    #[default]
+    Off,
+    Head,
+    Models,
+}
+
+impl FromStr for ReadinessProbe {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "head" => Ok(Self::Head),
+            "models" => Ok(Self::Models),
+            _ => Err("expected one of off, head, models".to_string()),
+        }
+    }
+}
+
+// file keys are the lowercase env names, env vars always win over the file.
+// each bool is an independent on/off env var, grouping them would need serde(flatten), which
+// doesn't work with deny_unknown_fields
+#[expect(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen: String,
+    pub openai_base_url: String,
+    pub haiku_model: String,
+    pub connection_timeout: u64,
+    pub idle_connection_timeout: u64,
+    pub ping_interval: u64,
+    pub upstream_max_retries: u32,
+    pub upstream_retry_base_delay_ms: u64,
+    pub upstream_retry_max_delay_ms: u64,
+    pub api_key_prefix: Option<String>,
+    pub max_request_body_bytes: usize,
+    pub shutdown_grace_period: u64,
+    pub readiness_probe: ReadinessProbe,
+    pub readiness_probe_key: Option<String>,
+    pub readiness_cache_secs: u64,
+    pub metrics_tenant_label: bool,
+    pub metrics_models: Vec<String>,
+    pub inline_image_urls: bool,
+    pub inline_image_hosts: Vec<String>,
+    pub defer_message_start: bool,
+    pub message_start_deadline_ms: u64,
+    pub estimate_usage: bool,
+    pub upstream_always_stream: bool,
+    pub disable_default_adapters: bool,
+    pub disable_groq_max_tokens: bool,
+    pub enable_reasoning_reentry: bool,
+    pub limit_directive_to_claudemd: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:33332".to_string(),
+            openai_base_url: "http://127.0.0.1:10152/v1".to_string(),
+            haiku_model: "openai/gpt-4.1-mini".to_string(),
+            connection_timeout: 10,
+            idle_connection_timeout: 60,
+            ping_interval: 30,
+            upstream_max_retries: 2,
+            upstream_retry_base_delay_ms: 500,
+            upstream_retry_max_delay_ms: 8000,
+            api_key_prefix: None,
+            max_request_body_bytes: 32 * 1024 * 1024,
+            shutdown_grace_period: 25,
+            readiness_probe: ReadinessProbe::Off,
+            readiness_probe_key: None,
+            readiness_cache_secs: 5,
+            metrics_tenant_label: false,
+            metrics_models: Vec::new(),
+            inline_image_urls: false,
+            inline_image_hosts: Vec::new(),
+            defer_message_start: false,
+            message_start_deadline_ms: 5000,
+            estimate_usage: true,
+            upstream_always_stream: false,
+            disable_default_adapters: false,
+            disable_groq_max_tokens: false,
+            enable_reasoning_reentry: false,
+            limit_directive_to_claudemd: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CliArgs {
+    pub config_path: Option<PathBuf>,
+    pub print_config: bool,
+}
+
+impl CliArgs {
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Args` for an unknown argument or a `--config` without a path.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--print-config" => parsed.print_config = true,
+                "--config" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| ConfigError::Args("--config requires a path".to_string()))?;
+                    parsed.config_path = Some(PathBuf::from(path));
+                }
+                other => {
+                    if let Some(path) = other.strip_prefix("--config=") {
+                        parsed.config_path = Some(PathBuf::from(path));
+                    } else {
+                        return Err(ConfigError::Args(format!("unknown argument {other}")));
+                    }
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+struct EnvOverrides<F> {
+    lookup: F,
+}
+
+impl<F: Fn(&str) -> Option<String>> EnvOverrides<F> {
+    // empty values are treated as unset, like the old `env::var(..).ok()` parsing did for numbers
+    fn get(&self, name: &str) -> Option<String> {
+        (self.lookup)(name).filter(|value| !value.trim().is_empty())
+    }
+
+    fn parsed<T>(&self, name: &'static str, target: &mut T) -> Result<(), ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Some(value) = self.get(name) {
+            *target = value
+                .trim()
+                .parse()
+                .map_err(|e: T::Err| ConfigError::InvalidEnv {
+                    name,
+                    value: value.clone(),
+                    reason: e.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+
+    fn list(&self, name: &str, target: &mut Vec<String>) {
+        if let Some(value) = self.get(name) {
+            *target = value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+
+    fn flag(&self, name: &'static str, target: &mut bool) -> Result<(), ConfigError> {
+        if let Some(value) = self.get(name) {
+            *target = parse_bool(value.trim()).ok_or_else(|| ConfigError::InvalidEnv {
+                name,
+                value: value.clone(),
+                reason: "expected one of 1, true, yes, on, 0, false, no, off".to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` when the config file can't be read or parsed, or when the result
+    /// is invalid.
+    pub fn load(cli: &CliArgs) -> Result<Self, ConfigError> {
+        let path = cli
+            .config_path
+            .clone()
+            .or_else(|| std::env::var_os("CONFIG_FILE").map(PathBuf::from));
+        let file = match path {
+            Some(path) => {
+                let contents = fs::read_to_string(&path).map_err(|source| ConfigError::Read {
+                    path: path.clone(),
+                    source,
+                })?;
+                Some((path, contents))
+            }
+            None => None,
+        };
+        Self::from_sources(
+            file.as_ref()
+                .map(|(path, contents)| (path.clone(), contents.as_str())),
+            |name| std::env::var(name).ok(),
+        )
+    }
+
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` when the file contents don't parse, an env var holds an invalid
+    /// value or the result is invalid.
+    pub fn from_sources(
+        file: Option<(PathBuf, &str)>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = match file {
+            Some((path, contents)) => {
+                toml::from_str(contents).map_err(|source| ConfigError::Parse { path, source })?
+            }
+            None => Self::default(),
+        };
+        config.apply_env(&EnvOverrides { lookup })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env<F: Fn(&str) -> Option<String>>(
+        &mut self,
+        env: &EnvOverrides<F>,
+    ) -> Result<(), ConfigError> {
+        env.parsed("LISTEN", &mut self.listen)?;
+        env.parsed("OPENAI_BASE_URL", &mut self.openai_base_url)?;
+        env.parsed("HAIKU_MODEL", &mut self.haiku_model)?;
+        env.parsed("CONNECTION_TIMEOUT", &mut self.connection_timeout)?;
+        env.parsed("IDLE_CONNECTION_TIMEOUT", &mut self.idle_connection_timeout)?;
+        env.parsed("PING_INTERVAL", &mut self.ping_interval)?;
+        env.parsed("UPSTREAM_MAX_RETRIES", &mut self.upstream_max_retries)?;
+        env.parsed(
+            "UPSTREAM_RETRY_BASE_DELAY_MS",
+            &mut self.upstream_retry_base_delay_ms,
+        )?;
+        env.parsed(
+            "UPSTREAM_RETRY_MAX_DELAY_MS",
+            &mut self.upstream_retry_max_delay_ms,
+        )?;
+        env.parsed("MAX_REQUEST_BODY_BYTES", &mut self.max_request_body_bytes)?;
+        env.parsed("SHUTDOWN_GRACE_PERIOD", &mut self.shutdown_grace_period)?;
+        env.parsed("READINESS_PROBE", &mut self.readiness_probe)?;
+        if let Some(key) = env.get("READINESS_PROBE_KEY") {
+            self.readiness_probe_key = Some(key);
+        }
+        env.parsed("READINESS_CACHE_SECS", &mut self.readiness_cache_secs)?;
+        if let Some(prefix) = env.get("API_KEY_PREFIX") {
+            self.api_key_prefix = Some(prefix);
+        }
+        env.flag("METRICS_TENANT_LABEL", &mut self.metrics_tenant_label)?;
+        env.list("METRICS_MODELS", &mut self.metrics_models);
+        env.flag("INLINE_IMAGE_URLS", &mut self.inline_image_urls)?;
+        env.list("INLINE_IMAGE_HOSTS", &mut self.inline_image_hosts);
+        env.flag("DEFER_MESSAGE_START", &mut self.defer_message_start)?;
+        env.parsed(
+            "MESSAGE_START_DEADLINE_MS",
+            &mut self.message_start_deadline_ms,
+        )?;
+        env.flag("ESTIMATE_USAGE", &mut self.estimate_usage)?;
+        env.flag("UPSTREAM_ALWAYS_STREAM", &mut self.upstream_always_stream)?;
+        env.flag(
+            "DISABLE_DEFAULT_ADAPTERS",
+            &mut self.disable_default_adapters,
+        )?;
+        env.flag("DISABLE_GROQ_MAX_TOKENS", &mut self.disable_groq_max_tokens)?;
+        env.flag(
+            "ENABLE_REASONING_REENTRY",
+            &mut self.enable_reasoning_reentry,
+        )?;
+        env.flag(
+            "LIMIT_DIRECTIVE_TO_CLAUDEMD",
+            &mut self.limit_directive_to_claudemd,
+        )?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.listen.trim().is_empty() {
+            return Err(ConfigError::Invalid("listen must not be empty".to_string()));
+        }
+        if !(self.openai_base_url.starts_with("http://")
+            || self.openai_base_url.starts_with("https://"))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "openai_base_url must start with http:// or https://, got {:?}",
+                self.openai_base_url
+            )));
+        }
+        if self.connection_timeout == 0 || self.idle_connection_timeout == 0 {
+            return Err(ConfigError::Invalid(
+                "connection_timeout and idle_connection_timeout must be greater than 0".to_string(),
+            ));
+        }
+        if self.ping_interval == 0 {
+            return Err(ConfigError::Invalid(
+                "ping_interval must be greater than 0".to_string(),
+            ));
+        }
+        if self.max_request_body_bytes == 0 {
+            return Err(ConfigError::Invalid(
+                "max_request_body_bytes must be greater than 0".to_string(),
+            ));
+        }
+        if self.readiness_probe == ReadinessProbe::Models && self.readiness_probe_key.is_none() {
+            return Err(ConfigError::Invalid(
+                "readiness_probe = \"models\" requires readiness_probe_key".to_string(),
+            ));
+        }
+        if self.upstream_retry_base_delay_ms > self.upstream_retry_max_delay_ms {
+            return Err(ConfigError::Invalid(format!(
+                "upstream_retry_base_delay_ms ({}) must not exceed upstream_retry_max_delay_ms ({})",
+                self.upstream_retry_base_delay_ms, self.upstream_retry_max_delay_ms
+            )));
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.upstream_max_retries,
+            base_delay: Duration::from_millis(self.upstream_retry_base_delay_ms),
+            max_delay: Duration::from_millis(self.upstream_retry_max_delay_ms),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Serialize` when the config can't be written as TOML.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        let mut redacted = self.clone();
+        if redacted.readiness_probe_key.is_some() {
+            redacted.readiness_probe_key = Some("<redacted>".to_string());
+        }
+        Ok(toml::to_string_pretty(&redacted)?)
+    }
+}
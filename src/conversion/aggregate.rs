@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+
+use crate::{
+    conversion::tool_arguments::parse_tool_arguments,
+    error::AppError,
+    models::claude::{AnthropicStreamEvent, ContentBlock, Delta},
+    telemetry::EventStream,
+};
+
+#[derive(Default)]
+struct PendingBlock {
+    block: Value,
+    arguments: String,
+}
+
+impl PendingBlock {
+    fn apply(&mut self, delta: Delta) {
+        let (field, fragment) = match delta {
+            Delta::Text { text } => ("text", text),
+            Delta::Thinking { thinking } => ("thinking", thinking),
+            Delta::Signature { signature } => ("signature", signature),
+            Delta::InputJson { partial_json } => {
+                self.arguments.push_str(&partial_json);
+                return;
+            }
+        };
+        let mut value = self.block[field].as_str().unwrap_or_default().to_string();
+        value.push_str(&fragment);
+        self.block[field] = Value::String(value);
+    }
+
+    fn finish(mut self) -> Option<Value> {
+        match self.block["type"].as_str() {
+            Some("tool_use") => {
+                self.block["input"] = parse_tool_arguments(&self.arguments);
+            }
+            // the non streaming path never returns empty text blocks either
+            Some("text") if self.block["text"].as_str().is_none_or(str::is_empty) => return None,
+            _ => {}
+        }
+        Some(self.block)
+    }
+}
+
+/// Folds the events of one streamed response into the message a non streaming client expects.
+///
+/// # Errors
+///
+/// Returns the first error of the event stream, such as an upstream failure or a truncated stream.
+pub async fn aggregate_events(mut events: EventStream) -> Result<Value, AppError> {
+    let mut message = json!({});
+    let mut blocks: BTreeMap<u32, PendingBlock> = BTreeMap::new();
+    while let Some(event) = events.next().await {
+        match event? {
+            AnthropicStreamEvent::MessageStart(start) => {
+                message = serde_json::to_value(start.message)?;
+            }
+            AnthropicStreamEvent::ContentBlockStart(start) => {
+                let block = match start.content_block {
+                    ContentBlock::ToolUse { id, name, .. } => {
+                        json!({ "type": "tool_use", "id": id, "name": name, "input": {} })
+                    }
+                    block => serde_json::to_value(block)?,
+                };
+                blocks.insert(
+                    start.index,
+                    PendingBlock {
+                        block,
+                        ..PendingBlock::default()
+                    },
+                );
+            }
+            AnthropicStreamEvent::ContentBlockDelta(delta) => {
+                if let Some(block) = blocks.get_mut(&delta.index) {
+                    block.apply(delta.delta);
+                }
+            }
+            AnthropicStreamEvent::MessageDelta(delta) => {
+                message["stop_reason"] = json!(delta.delta.stop_reason);
+                message["stop_sequence"] = json!(delta.delta.stop_sequence);
+                message["usage"] = serde_json::to_value(delta.usage)?;
+            }
+            _ => {}
+        }
+    }
+    message["content"] = blocks
+        .into_values()
+        .filter_map(PendingBlock::finish)
+        .collect();
+    Ok(message)
+}
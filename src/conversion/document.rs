@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    panic,
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::future::join_all;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::models::claude::{
+    ClaudeContent, ClaudeContentBlock, ClaudeMessagesRequest, DocumentSource,
+};
+
+// claude code resends the whole history every turn, a handful of recent documents covers it
+const MAX_CACHED_DOCUMENTS: usize = 64;
+
+type DocumentDigest = [u8; 32];
+
+// unreadable documents are cached too, a broken pdf fails the same way every turn
+#[derive(Clone)]
+enum Extracted {
+    Text(Arc<str>),
+    Unreadable,
+}
+
+impl Extracted {
+    fn new(text: Option<&str>) -> Self {
+        text.map_or(Self::Unreadable, |text| Self::Text(Arc::from(text)))
+    }
+
+    fn text(&self) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text.to_string()),
+            Self::Unreadable => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ExtractedDocuments {
+    texts: HashMap<DocumentDigest, Extracted>,
+    order: VecDeque<DocumentDigest>,
+}
+
+impl ExtractedDocuments {
+    fn insert(&mut self, digest: DocumentDigest, text: Extracted) {
+        if self.texts.insert(digest, text).is_none() {
+            self.order.push_back(digest);
+        }
+        while self.order.len() > MAX_CACHED_DOCUMENTS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.texts.remove(&oldest);
+            }
+        }
+    }
+}
+
+static EXTRACTED: LazyLock<Mutex<ExtractedDocuments>> = LazyLock::new(Mutex::default);
+
+fn digest(data: &str) -> DocumentDigest {
+    Sha256::digest(data.trim().as_bytes()).into()
+}
+
+fn cached(digest: &DocumentDigest) -> Option<Extracted> {
+    EXTRACTED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .texts
+        .get(digest)
+        .cloned()
+}
+
+fn remember(digest: DocumentDigest, text: Extracted) {
+    EXTRACTED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(digest, text);
+}
+
+// parsing a pdf is cpu bound and can take seconds, so it runs on the blocking pool before the
+// request is converted. conversion then only looks the text up
+pub async fn extract_documents(request: &ClaudeMessagesRequest) {
+    let mut pending = HashMap::new();
+    for (media_type, data) in request_documents(request) {
+        let digest = digest(&data);
+        if media_type == "application/pdf" && cached(&digest).is_none() {
+            pending.entry(digest).or_insert(data);
+        }
+    }
+    join_all(pending.into_iter().map(|(digest, data)| async move {
+        let text = tokio::task::spawn_blocking(move || parse_pdf(&data))
+            .await
+            .ok()
+            .flatten();
+        remember(digest, Extracted::new(text.as_deref()));
+    }))
+    .await;
+}
+
+fn request_documents(request: &ClaudeMessagesRequest) -> Vec<(String, String)> {
+    let mut documents = Vec::new();
+    for message in &request.messages {
+        if let ClaudeContent::Array(blocks) = &message.content {
+            collect_documents(blocks, &mut documents);
+        }
+    }
+    documents
+}
+
+fn collect_documents(blocks: &[ClaudeContentBlock], documents: &mut Vec<(String, String)>) {
+    for block in blocks {
+        match block.block_type.as_str() {
+            "document" => {
+                if let Some(DocumentSource::Base64 { media_type, data }) = block.document_source() {
+                    documents.push((media_type, data));
+                }
+            }
+            "tool_result" => {
+                if let Some(nested) = block
+                    .content
+                    .as_ref()
+                    .and_then(|content| Vec::<ClaudeContentBlock>::deserialize(content).ok())
+                {
+                    collect_documents(&nested, documents);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// pdf-extract panics on some malformed files, a bad attachment must not take the request down
+fn parse_pdf(data: &str) -> Option<String> {
+    let bytes = STANDARD.decode(data.trim()).ok()?;
+    match panic::catch_unwind(|| pdf_extract::extract_text_from_mem(&bytes)) {
+        Ok(Ok(text)) => Some(text.trim().to_string()),
+        Ok(Err(e)) => {
+            warn!("Failed to extract text from PDF document: {e}");
+            None
+        }
+        Err(_) => {
+            warn!("PDF text extraction panicked, the document is dropped");
+            None
+        }
+    }
+}
+
+// pdfs are parsed inline only when the caller skipped extract_documents
+#[must_use]
+pub fn extract_text(media_type: &str, data: &str) -> Option<String> {
+    if media_type == "application/pdf" {
+        let digest = digest(data);
+        if let Some(text) = cached(&digest) {
+            return text.text();
+        }
+        let text = parse_pdf(data);
+        remember(digest, Extracted::new(text.as_deref()));
+        text
+    } else if media_type.starts_with("text/") {
+        let bytes = STANDARD.decode(data.trim()).ok()?;
+        String::from_utf8(bytes)
+            .ok()
+            .map(|text| text.trim().to_string())
+    } else {
+        None
+    }
+}
+
+// client text must not be able to close the marker or open its own tags
+fn escape_markup(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// the marker keeps inlined documents apart from what the user typed
+#[must_use]
+pub fn document_text(
+    title: Option<&str>,
+    context: Option<&str>,
+    note: Option<&str>,
+    text: &str,
+) -> String {
+    let mut document = String::from("<document");
+    if let Some(title) = title {
+        let _ = write!(document, " title=\"{}\"", escape_markup(title));
+    }
+    if let Some(note) = note {
+        let _ = write!(document, " note=\"{}\"", escape_markup(note));
+    }
+    document.push_str(">\n");
+    if let Some(context) = context {
+        let _ = writeln!(document, "<context>{}</context>", escape_markup(context));
+    }
+    if !text.is_empty() {
+        document.push_str(text);
+        document.push('\n');
+    }
+    document.push_str("</document>");
+    document
+}
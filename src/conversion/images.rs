@@ -0,0 +1,246 @@
+use std::{
+    error::Error,
+    fmt::Write,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::{StreamExt, stream};
+use reqwest::{
+    Client, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+    redirect,
+};
+use tracing::warn;
+
+use crate::{
+    config::Config,
+    models::openai::{OpenAIContent, OpenAIImageUrl, OpenAIMessage},
+};
+
+// openai's own limit for a single image
+const MAX_INLINE_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+// the shared client only has a connect timeout, a stalled host must not hold the request forever
+const IMAGE_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_CONCURRENT_IMAGE_FETCHES: usize = 4;
+const MAX_IMAGE_REDIRECTS: usize = 5;
+
+// anything else reaches the proxy's own network, which clients must not be able to probe
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+// runs on the first url and on every redirect, names are checked again once they are resolved
+fn check_url(url: &Url, allowed_hosts: &[String]) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("only http and https urls can be downloaded".to_string());
+    }
+    let host = url.host_str().unwrap_or_default();
+    if allowed_hosts.iter().any(|allowed| allowed == host) {
+        return Ok(());
+    }
+    if !allowed_hosts.is_empty() {
+        return Err(format!("{host} is not an allowed image host"));
+    }
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) if !is_public(ip) => Err(format!("{ip} is not a public address")),
+        _ => Ok(()),
+    }
+}
+
+// filtering at resolve time means a second, rebound answer can't sneak a private address in
+struct PublicResolver {
+    allowed_hosts: Arc<[String]>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let trusted = self.allowed_hosts.contains(&host);
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| trusted || is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{host} does not resolve to a public address").into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+// a client of its own, so the checks apply to every hop without touching upstream requests
+#[derive(Clone)]
+pub struct ImageFetcher {
+    client: Client,
+    allowed_hosts: Arc<[String]>,
+}
+
+impl ImageFetcher {
+    /// Hosts in `inline_image_hosts` may resolve to private addresses, when the list is set no
+    /// other host is downloaded from.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reqwest error when the client can't be built.
+    pub fn new(config: &Config) -> reqwest::Result<Self> {
+        let allowed_hosts: Arc<[String]> = config.inline_image_hosts.clone().into();
+        let redirect_hosts = allowed_hosts.clone();
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(config.connection_timeout))
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver {
+                allowed_hosts: allowed_hosts.clone(),
+            }))
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > MAX_IMAGE_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if let Err(reason) = check_url(attempt.url(), &redirect_hosts) {
+                    attempt.error(reason)
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()?;
+        Ok(Self {
+            client,
+            allowed_hosts,
+        })
+    }
+}
+
+/// For upstreams that only accept data urls, remote images are downloaded and embedded before
+/// sending. An image that can't be downloaded in time, isn't an image, is larger than
+/// `MAX_INLINE_IMAGE_BYTES` or points at a private address becomes a text part saying so, since
+/// one dead link in the history would otherwise fail every later request of the conversation.
+pub async fn inline_image_urls(messages: &mut [OpenAIMessage], fetcher: &ImageFetcher) {
+    let image_parts: Vec<_> = messages
+        .iter_mut()
+        .filter_map(|message| match &mut message.content {
+            Some(OpenAIContent::Array(parts)) => Some(parts),
+            _ => None,
+        })
+        .flatten()
+        .filter(|part| {
+            part.image_url
+                .as_ref()
+                .is_some_and(|image_url| !image_url.url.starts_with("data:"))
+        })
+        .collect();
+    let urls: Vec<String> = image_parts
+        .iter()
+        .filter_map(|part| part.image_url.as_ref())
+        .map(|image_url| image_url.url.clone())
+        .collect();
+    let data_urls: Vec<_> = stream::iter(urls)
+        .map(|url| async move {
+            let data_url = fetch_data_url(fetcher, &url).await;
+            (url, data_url)
+        })
+        .buffered(MAX_CONCURRENT_IMAGE_FETCHES)
+        .collect()
+        .await;
+    for (part, (url, data_url)) in image_parts.into_iter().zip(data_urls) {
+        match data_url {
+            Ok(data_url) => part.image_url = Some(OpenAIImageUrl { url: data_url }),
+            Err(reason) => {
+                warn!("Unable to download the image at {url}: {reason}");
+                part.part_type = "text".to_string();
+                part.text = Some(format!(
+                    "[image at {url} could not be downloaded: {reason}]"
+                ));
+                part.image_url = None;
+            }
+        }
+    }
+}
+
+async fn fetch_data_url(fetcher: &ImageFetcher, url: &str) -> Result<String, String> {
+    let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+    check_url(&parsed, &fetcher.allowed_hosts)?;
+    let (media_type, bytes) =
+        tokio::time::timeout(IMAGE_FETCH_TIMEOUT, download_image(&fetcher.client, parsed))
+            .await
+            .map_err(|_| {
+                format!(
+                    "no complete response within {}s",
+                    IMAGE_FETCH_TIMEOUT.as_secs()
+                )
+            })??;
+    Ok(format!(
+        "data:{media_type};base64,{}",
+        STANDARD.encode(&bytes)
+    ))
+}
+
+// chunked responses have no content-length, so the size is checked while reading the body
+async fn download_image(client: &Client, url: Url) -> Result<(String, Vec<u8>), String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| error_chain(&e))?;
+    let media_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split_once(';')
+                .map_or(value, |(media_type, _)| media_type)
+        })
+        .map(str::trim)
+        .filter(|media_type| media_type.starts_with("image/"))
+        .map(ToString::to_string)
+        .ok_or_else(|| "the response is not an image".to_string())?;
+    let too_large = || format!("the image exceeds {MAX_INLINE_IMAGE_BYTES} bytes");
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_INLINE_IMAGE_BYTES as u64)
+    {
+        return Err(too_large());
+    }
+    let mut bytes = Vec::new();
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        if bytes.len() + chunk.len() > MAX_INLINE_IMAGE_BYTES {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok((media_type, bytes))
+}
+
+// reqwest's message leaves out why a request failed, the resolver and redirect reasons are sources
+fn error_chain(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(inner) = source {
+        let _ = write!(message, ": {inner}");
+        source = inner.source();
+    }
+    message
+}
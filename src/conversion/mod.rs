@@ -1,9 +1,17 @@
+pub mod aggregate;
+pub mod document;
+pub mod images;
+pub mod models;
 pub mod non_stream;
 pub mod request;
+pub mod server_tools;
 pub mod stream;
 pub mod think_parser;
+pub mod tool_arguments;
+pub mod tool_mapping;
+pub mod usage;
 
 pub use self::{
-    non_stream::convert_openai_to_claude, request::convert_claude_to_openai,
-    stream::convert_openai_stream_to_anthropic,
+    models::convert_openai_models_to_claude, non_stream::convert_openai_to_claude,
+    request::convert_claude_to_openai, stream::convert_openai_stream_to_anthropic,
 };
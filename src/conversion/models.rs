@@ -0,0 +1,68 @@
+use crate::{
+    adapters::defaults::tool_simulation::TOOL_SIMULATION_SUFFIXES,
+    models::{
+        claude::{ClaudeModelInfo, ClaudeModelList, ClaudeModelListQuery},
+        openai::{OpenAIModel, OpenAIModelList},
+    },
+    utils::format_unix_timestamp_rfc3339,
+};
+
+const DEFAULT_PAGE_LIMIT: usize = 20;
+const MAX_PAGE_LIMIT: usize = 1000;
+
+fn to_model_info(id: String, model: &OpenAIModel) -> ClaudeModelInfo {
+    ClaudeModelInfo {
+        model_type: "model".to_string(),
+        display_name: id.clone(),
+        id,
+        created_at: format_unix_timestamp_rfc3339(model.created.unwrap_or_default()),
+    }
+}
+
+// list the tool simulation variants next to each upstream model so they show up in model pickers
+fn expand_models(models: &[OpenAIModel]) -> Vec<ClaudeModelInfo> {
+    models
+        .iter()
+        .flat_map(|model| {
+            std::iter::once(to_model_info(model.id.clone(), model)).chain(
+                TOOL_SIMULATION_SUFFIXES
+                    .iter()
+                    .map(|suffix| to_model_info(format!("{}{suffix}", model.id), model)),
+            )
+        })
+        .collect()
+}
+
+#[must_use]
+pub fn convert_openai_models_to_claude(
+    model_list: &OpenAIModelList,
+    query: &ClaudeModelListQuery,
+) -> ClaudeModelList {
+    let models = expand_models(&model_list.data);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    let position = |id: &str| models.iter().position(|model| model.id == id);
+
+    let (start, end, has_more) = if let Some(before_id) = &query.before_id {
+        let end = position(before_id).unwrap_or(0);
+        let start = end.saturating_sub(limit);
+        (start, end, start > 0)
+    } else {
+        let start = match query.after_id.as_deref() {
+            Some(after_id) => position(after_id).map_or(models.len(), |index| index + 1),
+            None => 0,
+        };
+        let end = (start + limit).min(models.len());
+        (start, end, end < models.len())
+    };
+
+    let data = models[start..end].to_vec();
+    ClaudeModelList {
+        first_id: data.first().map(|model| model.id.clone()),
+        last_id: data.last().map(|model| model.id.clone()),
+        data,
+        has_more,
+    }
+}
@@ -1,8 +1,17 @@
-use super::think_parser::ThinkTagParser;
-use serde_json::{Map, Value, json};
+use super::{
+    think_parser::ThinkTagParser, tool_arguments::parse_tool_arguments, tool_mapping::ToolMapping,
+};
+use crate::{
+    config::Config,
+    models::{
+        claude::{FinishReason, find_stop_sequence},
+        openai::OpenAIUsage,
+    },
+};
+use serde_json::{Value, json};
 
-fn parse_text_blocks(input: &str) -> Vec<Value> {
-    let mut parser = ThinkTagParser::new();
+fn parse_text_blocks(input: &str, config: &Config) -> Vec<Value> {
+    let mut parser = ThinkTagParser::new(config.enable_reasoning_reentry);
     if input.is_empty() {
         return Vec::new();
     }
@@ -52,7 +61,13 @@ fn parse_text_blocks(input: &str) -> Vec<Value> {
 }
 
 #[must_use]
-pub fn convert_openai_to_claude(openai_response: &Value, model: &str) -> Value {
+pub fn convert_openai_to_claude(
+    openai_response: &Value,
+    model: &str,
+    stop_sequences: &[String],
+    tool_mapping: &ToolMapping,
+    config: &Config,
+) -> Value {
     let choice = &openai_response["choices"][0];
     let message = &choice["message"];
     let mut content_blocks = Vec::new();
@@ -68,34 +83,47 @@ pub fn convert_openai_to_claude(openai_response: &Value, model: &str) -> Value {
     }
 
     if let Some(content) = message["content"].as_str().filter(|s| !s.is_empty()) {
-        content_blocks.extend(parse_text_blocks(content));
+        content_blocks.extend(parse_text_blocks(content, config));
     }
 
     if let Some(tool_calls) = message["tool_calls"].as_array() {
         for tool_call in tool_calls {
-            if let (Some(id), Some(name), Some(arguments)) = (
-                tool_call["id"].as_str(),
+            if let (Some(name), Some(arguments)) = (
                 tool_call["function"]["name"].as_str(),
                 tool_call["function"]["arguments"].as_str(),
             ) {
-                let input: Value =
-                    serde_json::from_str(arguments).unwrap_or(Value::Object(Map::new()));
+                let input = parse_tool_arguments(arguments);
                 content_blocks.push(json!({
                     "type": "tool_use",
-                    "id": id,
-                    "name": name,
+                    "id": tool_mapping.client_id(tool_call["id"].as_str()),
+                    "name": tool_mapping.client_name(name),
                     "input": input
                 }));
             }
         }
     }
 
-    let stop_reason = match choice["finish_reason"].as_str() {
-        Some("length") => "max_tokens",
-        Some("tool_calls") => "tool_use",
-        Some("content_filter") => "stop_sequence",
-        _ => "end_turn",
+    let finish_reason = choice["finish_reason"].as_str();
+    let stop_sequence = if finish_reason == Some("stop") {
+        let upstream_match = choice["stop_reason"]
+            .as_str()
+            .or_else(|| choice["matched_stop"].as_str());
+        find_stop_sequence(
+            stop_sequences,
+            upstream_match,
+            message["content"].as_str().unwrap_or_default(),
+        )
+    } else {
+        None
     };
+    let stop_reason = if stop_sequence.is_some() {
+        "stop_sequence"
+    } else {
+        FinishReason(finish_reason).to_anthropic_stop_reason()
+    };
+
+    let usage: OpenAIUsage =
+        serde_json::from_value(openai_response["usage"].clone()).unwrap_or_default();
 
     json!({
         "id": openai_response["id"],
@@ -104,9 +132,7 @@ pub fn convert_openai_to_claude(openai_response: &Value, model: &str) -> Value {
         "model": model,
         "content": content_blocks,
         "stop_reason": stop_reason,
-        "usage": {
-            "input_tokens": openai_response["usage"]["prompt_tokens"],
-            "output_tokens": openai_response["usage"]["completion_tokens"]
-        }
+        "stop_sequence": stop_sequence,
+        "usage": usage.to_claude_usage()
     })
 }
@@ -1,21 +1,37 @@
 use crate::adapters::RequestAdapter;
-use tracing::debug;
+use serde_json::Value;
+use tracing::{debug, warn};
 pub type Request = ClaudeMessagesRequest;
 use crate::{
+    conversion::{
+        document::{document_text, extract_text},
+        server_tools::function_tools,
+        tool_mapping::ToolMapping,
+    },
+    directives::models::{DocumentMode, ThinkingHistory},
     models::{
         claude::{
             ClaudeContent, ClaudeContentBlock, ClaudeMessage, ClaudeMessagesRequest, ClaudeSystem,
-            ClaudeTool, ClaudeToolChoice,
+            ClaudeTool, ClaudeToolChoice, DocumentSource, ImageSource,
         },
         openai::{
-            OpenAIContent, OpenAIContentPart, OpenAIFunction, OpenAIFunctionChoice, OpenAIImageUrl,
-            OpenAIMessage, OpenAIRequest, OpenAITool, OpenAIToolCall, OpenAIToolChoice,
-            OpenAIToolFunction, StreamOptions,
+            OpenAIContent, OpenAIContentPart, OpenAIFile, OpenAIFunction, OpenAIFunctionChoice,
+            OpenAIImageUrl, OpenAIMessage, OpenAIRequest, OpenAITool, OpenAIToolCall,
+            OpenAIToolChoice, OpenAIToolFunction, StreamOptions,
         },
     },
     utils::map_budget_tokens_to_reasoning_effort,
 };
 
+// chat completions only accepts text in tool messages, so images default to a follow-up user message.
+// the responses api takes them as part of the function_call_output instead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolResultImages {
+    #[default]
+    UserMessage,
+    ToolContent,
+}
+
 #[must_use]
 pub fn convert_claude_to_openai(
     claude_request: ClaudeMessagesRequest,
@@ -25,35 +41,21 @@ pub fn convert_claude_to_openai(
     let req_clone = claude_request.clone();
     let mut messages = Vec::new();
 
-    if let Some(system) = &claude_request.system {
-        let mut system_content = match system {
-            ClaudeSystem::Text(text) => text.clone(),
-            ClaudeSystem::Array(blocks) => blocks
-                .iter()
-                .filter_map(|block| {
-                    if block.block_type == "text" {
-                        block.text.as_ref()
-                    } else {
-                        None
-                    }
-                })
-                .cloned()
-                .collect::<Vec<String>>()
-                .join("\n"),
-        };
-
-        system_content = adapter.adapt_system_prompt(&system_content, &req_clone);
-        if !system_content.is_empty() {
-            messages.push(OpenAIMessage {
-                role: "system".to_string(),
-                content: Some(OpenAIContent::Text(system_content)),
-                ..Default::default()
-            });
-        }
+    if let Some(system) = &claude_request.system
+        && let Some(message) = convert_claude_system_prompt(system, adapter, &req_clone)
+    {
+        messages.push(message);
     }
 
+    let tool_mapping = ToolMapping::new(adapter.tool_naming(&req_clone), &req_clone);
     for message in &claude_request.messages {
-        convert_claude_message_to_openai(message, &mut messages, adapter, &req_clone);
+        convert_claude_message_to_openai(
+            message,
+            &mut messages,
+            adapter,
+            &req_clone,
+            &tool_mapping,
+        );
     }
 
     let reasoning_effort = claude_request
@@ -73,6 +75,7 @@ pub fn convert_claude_to_openai(
         stop: claude_request.stop_sequences,
         tools: None,
         tool_choice: None,
+        parallel_tool_calls: None,
         reasoning_effort,
         stream_options: if claude_request.stream.unwrap_or(false) {
             Some(StreamOptions {
@@ -81,18 +84,53 @@ pub fn convert_claude_to_openai(
         } else {
             None
         },
+        top_k: adapter.adapt_top_k(claude_request.top_k, &req_clone),
+        user: claude_request
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.user_id.clone()),
+        service_tier: claude_request
+            .service_tier
+            .as_deref()
+            .map(convert_claude_service_tier_to_openai),
     };
 
-    let adapted_tools = adapter.adapt_tools(claude_request.tools, &req_clone);
+    let function_tools = function_tools(&req_clone);
+    let adapted_tools = adapter.adapt_tools(function_tools.clone(), &req_clone);
     if let Some(tools) = adapted_tools
         && !tools.is_empty()
     {
-        openai_request.tools = Some(convert_claude_tools_to_openai(tools, adapter, &req_clone));
+        openai_request.tools = Some(convert_claude_tools_to_openai(
+            tools,
+            adapter,
+            &req_clone,
+            &tool_mapping,
+        ));
     }
 
-    let adapted_tool_choice = adapter.adapt_tool_choice(claude_request.tool_choice, &req_clone);
+    // a choice naming a dropped server tool can't be forced upstream
+    let tool_choice = claude_request.tool_choice.map(|mut tool_choice| {
+        if let Some(name) = tool_choice.name.as_deref()
+            && !function_tools
+                .iter()
+                .flatten()
+                .any(|tool| tool.name == name)
+        {
+            tool_choice.choice_type = "auto".to_string();
+            tool_choice.name = None;
+        }
+        tool_choice
+    });
+    let adapted_tool_choice = adapter.adapt_tool_choice(tool_choice, &req_clone);
     if let Some(tool_choice) = adapted_tool_choice {
-        openai_request.tool_choice = Some(convert_claude_tool_choice_to_openai(tool_choice));
+        // upstreams reject parallel_tool_calls on a request without tools
+        if tool_choice.disable_parallel_tool_use == Some(true) && openai_request.tools.is_some() {
+            openai_request.parallel_tool_calls = Some(false);
+        }
+        openai_request.tool_choice = Some(convert_claude_tool_choice_to_openai(
+            tool_choice,
+            &tool_mapping,
+        ));
     }
 
     openai_request.temperature = adapter.adapt_temperature(openai_request.temperature, &req_clone);
@@ -106,11 +144,53 @@ pub fn convert_claude_to_openai(
     openai_request
 }
 
+fn convert_claude_system_prompt(
+    system: &ClaudeSystem,
+    adapter: &RequestAdapter,
+    request: &Request,
+) -> Option<OpenAIMessage> {
+    let system_content = match system {
+        ClaudeSystem::Text(text) => text.clone(),
+        ClaudeSystem::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| {
+                if block.block_type == "text" {
+                    block.text.as_ref()
+                } else {
+                    None
+                }
+            })
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n"),
+    };
+
+    let system_content = adapter.adapt_system_prompt(&system_content, request);
+    if system_content.is_empty() {
+        return None;
+    }
+    let content = match system {
+        ClaudeSystem::Array(blocks)
+            if adapter.forwards_cache_control(request)
+                && blocks.iter().any(|block| block.cache_control.is_some()) =>
+        {
+            OpenAIContent::Array(split_system_at_breakpoints(&system_content, blocks))
+        }
+        _ => OpenAIContent::Text(system_content),
+    };
+    Some(OpenAIMessage {
+        role: "system".to_string(),
+        content: Some(content),
+        ..Default::default()
+    })
+}
+
 fn convert_claude_message_to_openai(
     message: &ClaudeMessage,
     messages: &mut Vec<OpenAIMessage>,
     adapter: &RequestAdapter,
     request: &Request,
+    tool_mapping: &ToolMapping,
 ) {
     let mut adapted_message = message.clone();
     if let ("user", ClaudeContent::Text(text)) =
@@ -119,8 +199,19 @@ fn convert_claude_message_to_openai(
         adapted_message.content = ClaudeContent::Text(adapter.adapt_user_prompt(text, request));
     }
     match adapted_message.role.as_str() {
-        "user" => convert_claude_user_message(adapted_message.content, messages, adapter, request),
-        "assistant" => convert_claude_assistant_message(adapted_message.content, messages),
+        "user" => convert_claude_user_message(
+            adapted_message.content,
+            messages,
+            adapter,
+            request,
+            tool_mapping,
+        ),
+        "assistant" => convert_claude_assistant_message(
+            adapted_message.content,
+            messages,
+            adapter.thinking_history(request),
+            tool_mapping,
+        ),
         _ => {}
     }
 }
@@ -130,18 +221,18 @@ fn convert_claude_user_message(
     messages: &mut Vec<OpenAIMessage>,
     adapter: &RequestAdapter,
     request: &Request,
+    tool_mapping: &ToolMapping,
 ) {
     match content {
         ClaudeContent::Text(text) => {
             messages.push(OpenAIMessage {
                 role: "user".to_string(),
                 content: Some(OpenAIContent::Text(text)),
-                tool_calls: None,
-                tool_call_id: None,
+                ..Default::default()
             });
         }
         ClaudeContent::Array(blocks) => {
-            convert_claude_content_blocks(&blocks, messages, adapter, request);
+            convert_claude_content_blocks(&blocks, messages, adapter, request, tool_mapping);
         }
     }
 }
@@ -151,21 +242,28 @@ fn convert_claude_content_blocks(
     messages: &mut Vec<OpenAIMessage>,
     adapter: &RequestAdapter,
     request: &Request,
+    tool_mapping: &ToolMapping,
 ) {
     let tool_results: Vec<_> = blocks
         .iter()
         .filter(|b| b.block_type == "tool_result")
         .collect();
 
+    let image_placement = adapter.tool_result_images(request);
+    let document_mode = adapter.document_mode(request);
+    let forward_cache_control = adapter.forwards_cache_control(request);
+    let mut tool_images = Vec::new();
+
     for block in &tool_results {
         if let (Some(tool_use_id), Some(content)) = (&block.tool_use_id, &block.content) {
             let tool_name = request
                 .find_tool_name_by_id(tool_use_id)
                 .unwrap_or_default();
-            let final_content = if let serde_json::Value::String(s) = content {
-                adapter.adapt_tool_result(&tool_name, s, request)
-            } else {
-                serde_json::to_string(content).unwrap_or_default()
+            let tool_call_id = tool_mapping.upstream_id(tool_use_id);
+            let (text, images) = split_tool_result_content(content, document_mode);
+            let final_content = match text {
+                Some(text) => adapter.adapt_tool_result(&tool_name, &text, request),
+                None => serde_json::to_string(content).unwrap_or_default(),
             };
             debug!(
                 "
@@ -173,53 +271,323 @@ fn convert_claude_content_blocks(
 {final_content}
 --- END ADAPTED TOOL RESULT ---"
             );
+            let tool_content = if images.is_empty() {
+                OpenAIContent::Text(final_content)
+            } else if image_placement == ToolResultImages::ToolContent {
+                let mut parts = vec![text_part(final_content)];
+                parts.extend(images);
+                OpenAIContent::Array(parts)
+            } else {
+                let (label, kind) = if images.iter().all(|part| part.image_url.is_some()) {
+                    ("Images", "images")
+                } else {
+                    ("Files", "files")
+                };
+                tool_images.push(text_part(format!(
+                    "{label} returned by the {} tool call {tool_call_id}:",
+                    tool_mapping.upstream_name(&tool_name)
+                )));
+                tool_images.extend(images);
+                OpenAIContent::Text(if final_content.is_empty() {
+                    format!("The {kind} are attached in the next message.")
+                } else {
+                    final_content
+                })
+            };
+            let tool_content = match &block.cache_control {
+                Some(cache_control) if forward_cache_control => {
+                    tool_content_with_cache_control(tool_content, cache_control)
+                }
+                _ => tool_content,
+            };
             messages.push(OpenAIMessage {
                 role: "tool".to_string(),
-                content: Some(OpenAIContent::Text(final_content)),
-                tool_calls: None,
-                tool_call_id: Some(tool_use_id.clone()),
+                content: Some(tool_content),
+                tool_call_id: Some(tool_call_id),
+                ..Default::default()
             });
         }
     }
 
-    let other_content: Vec<_> = blocks
+    let other_parts = blocks
         .iter()
         .filter(|b| b.block_type != "tool_result")
-        .collect();
+        .flat_map(|block| {
+            let mut parts: Vec<OpenAIContentPart> = match block.block_type.as_str() {
+                "text" => block
+                    .text
+                    .as_ref()
+                    .map(|text| text_part(adapter.adapt_user_prompt(text, request)))
+                    .into_iter()
+                    .collect(),
+                "image" => block
+                    .image_source()
+                    .as_ref()
+                    .map(image_part)
+                    .into_iter()
+                    .collect(),
+                "document" => document_parts(block, document_mode),
+                _ => Vec::new(),
+            };
+            // the breakpoint belongs after everything the block turned into
+            if forward_cache_control && let Some(last) = parts.last_mut() {
+                last.cache_control.clone_from(&block.cache_control);
+            }
+            parts
+        });
+    // tool images go after every tool message, upstreams reject a user turn between tool results
+    let content_parts: Vec<OpenAIContentPart> =
+        tool_images.into_iter().chain(other_parts).collect();
 
-    if !other_content.is_empty() {
-        let content_parts: Vec<OpenAIContentPart> = other_content
-            .iter()
-            .filter_map(|block| match block.block_type.as_str() {
-                "text" => block.text.as_ref().map(|text| OpenAIContentPart {
-                    part_type: "text".to_string(),
-                    text: Some(adapter.adapt_user_prompt(text, request)),
-                    image_url: None,
-                }),
-                "image" => block.source.as_ref().map(|source| OpenAIContentPart {
-                    part_type: "image_url".to_string(),
-                    text: None,
-                    image_url: Some(OpenAIImageUrl {
-                        url: format!("data:{};base64,{}", source.media_type, source.data),
-                    }),
-                }),
-                _ => None,
-            })
-            .collect();
+    if !content_parts.is_empty() {
+        messages.push(OpenAIMessage {
+            role: "user".to_string(),
+            content: Some(OpenAIContent::Array(content_parts)),
+            ..Default::default()
+        });
+    }
+}
 
-        if !content_parts.is_empty() {
-            messages.push(OpenAIMessage {
-                role: "user".to_string(),
-                content: Some(OpenAIContent::Array(content_parts)),
-                tool_calls: None,
-                tool_call_id: None,
-            });
+fn text_part(text: String) -> OpenAIContentPart {
+    OpenAIContentPart {
+        part_type: "text".to_string(),
+        text: Some(text),
+        image_url: None,
+        file: None,
+        cache_control: None,
+    }
+}
+
+// a breakpoint needs a content part to sit on, so a plain text tool result becomes a single part
+fn tool_content_with_cache_control(content: OpenAIContent, cache_control: &Value) -> OpenAIContent {
+    let mut parts = match content {
+        OpenAIContent::Text(text) => vec![text_part(text)],
+        OpenAIContent::Array(parts) => parts,
+    };
+    if let Some(last) = parts.last_mut() {
+        last.cache_control = Some(cache_control.clone());
+    }
+    OpenAIContent::Array(parts)
+}
+
+fn with_cache_control(
+    mut part: OpenAIContentPart,
+    cache_control: Option<Value>,
+) -> OpenAIContentPart {
+    part.cache_control = cache_control;
+    part
+}
+
+// adapters rewrite the joined prompt, so each block is looked up again and the prompt is split after
+// the ones with a breakpoint. a breakpoint on a block an adapter changed moves to the next split,
+// caching a longer prefix is still correct
+fn split_system_at_breakpoints(
+    system_prompt: &str,
+    blocks: &[ClaudeContentBlock],
+) -> Vec<OpenAIContentPart> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut cursor = 0;
+    let mut pending = None;
+    for block in blocks.iter().filter(|block| block.block_type == "text") {
+        if block.cache_control.is_some() {
+            pending.clone_from(&block.cache_control);
+        }
+        let Some(text) = block.text.as_deref().filter(|text| !text.is_empty()) else {
+            continue;
+        };
+        if let Some(offset) = system_prompt[cursor..].find(text) {
+            cursor += offset + text.len();
+            if pending.is_some() {
+                parts.push(with_cache_control(
+                    text_part(system_prompt[start..cursor].to_string()),
+                    pending.take(),
+                ));
+                start = cursor;
+            }
+        }
+    }
+    let rest = &system_prompt[start..];
+    if !rest.trim().is_empty() || parts.is_empty() {
+        parts.push(with_cache_control(text_part(rest.to_string()), pending));
+    } else if let (Some(cache_control), Some(last)) = (pending, parts.last_mut()) {
+        last.cache_control = Some(cache_control);
+    }
+    parts
+}
+
+fn image_part(source: &ImageSource) -> OpenAIContentPart {
+    let url = match source {
+        ImageSource::Base64 { media_type, data } => format!("data:{media_type};base64,{data}"),
+        ImageSource::Url { url } => url.clone(),
+        // anthropic file ids mean nothing to other upstreams
+        ImageSource::File { file_id } => {
+            warn!("Dropping image {file_id} from the Files API, it can't be sent upstream");
+            return text_part(format!(
+                "[image {file_id} was uploaded through the Anthropic Files API and is not available]"
+            ));
         }
+    };
+    OpenAIContentPart {
+        part_type: "image_url".to_string(),
+        text: None,
+        image_url: Some(OpenAIImageUrl { url }),
+        file: None,
+        cache_control: None,
+    }
+}
+
+// openai rejects file_data without a filename
+fn default_filename(media_type: &str) -> String {
+    let extension = match media_type {
+        "application/pdf" => "pdf",
+        "text/plain" => "txt",
+        _ => media_type
+            .rsplit_once('/')
+            .map_or("bin", |(_, subtype)| subtype),
+    };
+    format!("document.{extension}")
+}
+
+fn file_part(filename: Option<String>, media_type: &str, data: &str) -> OpenAIContentPart {
+    OpenAIContentPart {
+        part_type: "file".to_string(),
+        text: None,
+        image_url: None,
+        file: Some(OpenAIFile {
+            filename: Some(filename.unwrap_or_else(|| default_filename(media_type))),
+            file_data: format!("data:{media_type};base64,{data}"),
+        }),
+        cache_control: None,
     }
 }
 
-fn convert_claude_assistant_message(content: ClaudeContent, messages: &mut Vec<OpenAIMessage>) {
+fn document_parts(block: &ClaudeContentBlock, mode: DocumentMode) -> Vec<OpenAIContentPart> {
+    let Some(source) = block.document_source() else {
+        debug!("Dropping document block with an unsupported source");
+        return Vec::new();
+    };
+    let title = block.title.as_deref();
+    let context = block.context.as_deref();
+    match source {
+        DocumentSource::Text { data } => {
+            vec![text_part(document_text(title, context, None, &data))]
+        }
+        DocumentSource::Content {
+            content: ClaudeContent::Text(text),
+        } => vec![text_part(document_text(title, context, None, &text))],
+        DocumentSource::Content {
+            content: ClaudeContent::Array(blocks),
+        } => {
+            let text = blocks
+                .iter()
+                .filter(|block| block.block_type == "text")
+                .filter_map(|block| block.text.as_deref())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let images = blocks
+                .iter()
+                .filter(|block| block.block_type == "image")
+                .filter_map(ClaudeContentBlock::image_source)
+                .map(|source| image_part(&source));
+            std::iter::once(text_part(document_text(title, context, None, &text)))
+                .chain(images)
+                .collect()
+        }
+        DocumentSource::Base64 { media_type, data } if mode == DocumentMode::File => {
+            let mut parts = Vec::new();
+            if let Some(context) = context {
+                parts.push(text_part(document_text(title, Some(context), None, "")));
+            }
+            parts.push(file_part(
+                title.map(ToString::to_string),
+                &media_type,
+                &data,
+            ));
+            parts
+        }
+        DocumentSource::Base64 { media_type, data } => {
+            let text = match extract_text(&media_type, &data) {
+                Some(text) => document_text(
+                    title,
+                    context,
+                    Some(&format!(
+                        "text extracted from {media_type}, layout and images are lost"
+                    )),
+                    &text,
+                ),
+                None => document_text(
+                    title,
+                    context,
+                    Some(&format!(
+                        "{media_type} attachment, its text could not be extracted"
+                    )),
+                    "",
+                ),
+            };
+            vec![text_part(text)]
+        }
+        DocumentSource::Url { url } => vec![text_part(document_text(
+            title,
+            context,
+            Some("the document was referenced by url and not fetched"),
+            &url,
+        ))],
+    }
+}
+
+// returns None for the text when the content isn't a string or a block array, it is passed through as json
+fn split_tool_result_content(
+    content: &Value,
+    document_mode: DocumentMode,
+) -> (Option<String>, Vec<OpenAIContentPart>) {
+    match content {
+        Value::String(text) => (Some(text.clone()), Vec::new()),
+        Value::Array(_) => {
+            let Ok(blocks) = serde_json::from_value::<Vec<ClaudeContentBlock>>(content.clone())
+            else {
+                return (None, Vec::new());
+            };
+            let mut texts = Vec::new();
+            let mut attachments = Vec::new();
+            for block in &blocks {
+                match block.block_type.as_str() {
+                    "text" => texts.extend(block.text.clone()),
+                    "image" | "document" => {
+                        let parts = if block.block_type == "image" {
+                            block
+                                .image_source()
+                                .as_ref()
+                                .map(image_part)
+                                .into_iter()
+                                .collect()
+                        } else {
+                            document_parts(block, document_mode)
+                        };
+                        for part in parts {
+                            match part.text {
+                                Some(text) => texts.push(text),
+                                None => attachments.push(part),
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (Some(texts.join("\n")), attachments)
+        }
+        _ => (None, Vec::new()),
+    }
+}
+
+fn convert_claude_assistant_message(
+    content: ClaudeContent,
+    messages: &mut Vec<OpenAIMessage>,
+    thinking_history: ThinkingHistory,
+    tool_mapping: &ToolMapping,
+) {
     let mut text_parts = Vec::new();
+    let mut thinking_parts = Vec::new();
     let mut tool_calls = Vec::new();
 
     match content {
@@ -234,15 +602,24 @@ fn convert_claude_assistant_message(content: ClaudeContent, messages: &mut Vec<O
                             text_parts.push(text);
                         }
                     }
+                    "thinking" => {
+                        if let Some(thinking) = block.thinking {
+                            thinking_parts.push(thinking);
+                        }
+                    }
+                    // redacted thinking is encrypted by anthropic, other upstreams can't read it
+                    "redacted_thinking" => {
+                        debug!("Dropping redacted_thinking block from assistant history");
+                    }
                     "tool_use" => {
                         if let (Some(id), Some(name), Some(input)) =
                             (block.id, block.name, block.input)
                         {
                             tool_calls.push(OpenAIToolCall {
-                                id,
+                                id: tool_mapping.upstream_id(&id),
                                 call_type: "function".to_string(),
                                 function: OpenAIFunction {
-                                    name,
+                                    name: tool_mapping.upstream_name(&name),
                                     arguments: serde_json::to_string(&input).unwrap_or_default(),
                                 },
                             });
@@ -254,6 +631,18 @@ fn convert_claude_assistant_message(content: ClaudeContent, messages: &mut Vec<O
         }
     }
 
+    let thinking = (!thinking_parts.is_empty()).then(|| thinking_parts.join("\n"));
+    let mut reasoning_content = None;
+    let mut reasoning = None;
+    match (thinking_history, thinking) {
+        (ThinkingHistory::ReasoningContent, Some(thinking)) => reasoning_content = Some(thinking),
+        (ThinkingHistory::Reasoning, Some(thinking)) => reasoning = Some(thinking),
+        (ThinkingHistory::Inline, Some(thinking)) => {
+            text_parts.insert(0, format!("<think>{thinking}</think>"));
+        }
+        _ => {}
+    }
+
     // WORKAROUND: Unable to submit request because it must include at least one parts field, which describes the prompt input. Learn more: https://cloud.google.com/vertex-ai/generative-ai/docs/model-reference/gemini
     if text_parts.is_empty() && tool_calls.is_empty() {
         return;
@@ -274,6 +663,8 @@ fn convert_claude_assistant_message(content: ClaudeContent, messages: &mut Vec<O
             Some(tool_calls)
         },
         tool_call_id: None,
+        reasoning_content,
+        reasoning,
     });
 }
 
@@ -281,7 +672,9 @@ fn convert_claude_tools_to_openai(
     tools: Vec<ClaudeTool>,
     adapter: &RequestAdapter,
     request: &Request,
+    tool_mapping: &ToolMapping,
 ) -> Vec<OpenAITool> {
+    let forward_cache_control = adapter.forwards_cache_control(request);
     tools
         .into_iter()
         .map(|tool| {
@@ -294,27 +687,43 @@ fn convert_claude_tools_to_openai(
             OpenAITool {
                 tool_type: "function".to_string(),
                 function: OpenAIToolFunction {
-                    name: tool.name,
+                    name: tool_mapping.upstream_name(&tool.name),
                     description: Some(description),
                     parameters,
                 },
+                cache_control: tool.cache_control.filter(|_| forward_cache_control),
             }
         })
         .collect()
 }
 
-fn convert_claude_tool_choice_to_openai(tool_choice: ClaudeToolChoice) -> OpenAIToolChoice {
+// anthropic only has auto and standard_only, anything else is passed through for upstream specific tiers
+fn convert_claude_service_tier_to_openai(service_tier: &str) -> String {
+    match service_tier {
+        "standard_only" => "default".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn convert_claude_tool_choice_to_openai(
+    tool_choice: ClaudeToolChoice,
+    tool_mapping: &ToolMapping,
+) -> OpenAIToolChoice {
     match tool_choice.choice_type.as_str() {
         "tool" => {
             if let Some(name) = tool_choice.name {
                 OpenAIToolChoice::Object {
                     choice_type: "function".to_string(),
-                    function: OpenAIFunctionChoice { name },
+                    function: OpenAIFunctionChoice {
+                        name: tool_mapping.upstream_name(&name),
+                    },
                 }
             } else {
-                OpenAIToolChoice::String("auto".to_string())
+                OpenAIToolChoice::String("required".to_string())
             }
         }
+        "any" => OpenAIToolChoice::String("required".to_string()),
+        "none" => OpenAIToolChoice::String("none".to_string()),
         _ => OpenAIToolChoice::String("auto".to_string()),
     }
 }
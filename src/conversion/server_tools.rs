@@ -0,0 +1,107 @@
+use serde_json::{Value, json};
+
+use crate::{
+    conversion::request::Request,
+    models::claude::{ClaudeServerTool, ClaudeTool, ClaudeToolDefinition},
+};
+
+const BASH_DESCRIPTION: &str = "Run a command in a persistent bash shell. State like the working directory and environment variables is kept between calls. Set `restart` to start a fresh shell.";
+const TEXT_EDITOR_DESCRIPTION: &str = "View, create and edit text files. `view` shows a file with line numbers or lists a directory, `create` writes `file_text` to a new file, `str_replace` replaces the unique occurrence of `old_str` with `new_str`, and `insert` adds text after line `insert_line` (0 for the start of the file).";
+
+fn bash_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "command": { "type": "string", "description": "The bash command to run" },
+            "restart": { "type": "boolean", "description": "Restart the shell instead of running a command" }
+        }
+    })
+}
+
+// the first two versions still had undo_edit and took the inserted text as new_str
+fn text_editor_schema(tool_type: &str) -> Value {
+    let legacy = matches!(tool_type, "text_editor_20241022" | "text_editor_20250124");
+    let mut commands = vec!["view", "create", "str_replace", "insert"];
+    if legacy {
+        commands.push("undo_edit");
+    }
+    let mut schema = json!({
+        "type": "object",
+        "properties": {
+            "command": { "type": "string", "enum": commands },
+            "path": { "type": "string", "description": "Path of the file or directory" },
+            "file_text": { "type": "string", "description": "Content of the file to create" },
+            "old_str": { "type": "string", "description": "Exact text to replace, must be unique in the file" },
+            "new_str": { "type": "string", "description": "Replacement text" },
+            "insert_line": { "type": "integer", "description": "Line after which to insert the text" },
+            "view_range": {
+                "type": "array",
+                "items": { "type": "integer" },
+                "description": "Start and end line to view, -1 as end reads to the end of the file"
+            }
+        },
+        "required": ["command", "path"]
+    });
+    if !legacy {
+        schema["properties"]["insert_text"] =
+            json!({ "type": "string", "description": "Text to insert" });
+    }
+    schema
+}
+
+// client executed anthropic tools only lack a schema, the upstream can call them like any function
+#[must_use]
+pub fn function_equivalent(tool: &ClaudeServerTool) -> Option<ClaudeTool> {
+    let (description, input_schema) = match tool.kind() {
+        "bash" => (BASH_DESCRIPTION, bash_schema()),
+        "text_editor" => (TEXT_EDITOR_DESCRIPTION, text_editor_schema(&tool.tool_type)),
+        _ => return None,
+    };
+    Some(ClaudeTool {
+        name: tool.name.clone(),
+        description: Some(description.to_string()),
+        input_schema,
+        cache_control: tool.options.get("cache_control").cloned(),
+    })
+}
+
+// custom tools plus the server tools that can be sent as functions
+#[must_use]
+pub fn function_tools(request: &Request) -> Option<Vec<ClaudeTool>> {
+    request.tools.as_ref().map(|tools| {
+        tools
+            .iter()
+            .filter_map(|tool| match tool {
+                ClaudeToolDefinition::Custom(tool) => Some(tool.clone()),
+                ClaudeToolDefinition::Server(tool) => function_equivalent(tool),
+            })
+            .collect()
+    })
+}
+
+pub fn server_tools(request: &Request) -> impl Iterator<Item = &ClaudeServerTool> {
+    request
+        .tools
+        .iter()
+        .flatten()
+        .filter_map(|tool| match tool {
+            ClaudeToolDefinition::Server(tool) => Some(tool),
+            ClaudeToolDefinition::Custom(_) => None,
+        })
+}
+
+// the responses api runs its own web search, anthropic's allowed_domains and user_location carry over
+#[must_use]
+pub fn responses_web_search(tool: &ClaudeServerTool) -> Option<Value> {
+    if tool.kind() != "web_search" {
+        return None;
+    }
+    let mut web_search = json!({ "type": "web_search" });
+    if let Some(domains) = tool.options.get("allowed_domains") {
+        web_search["filters"] = json!({ "allowed_domains": domains });
+    }
+    if let Some(location) = tool.options.get("user_location") {
+        web_search["user_location"] = location.clone();
+    }
+    Some(web_search)
+}
@@ -13,20 +13,30 @@ use tracing::{debug, error};
 
 use crate::{
     adapters::RequestAdapter,
-    error::AppError,
+    config::Config,
+    conversion::{
+        think_parser::ThinkTagParser, tool_arguments::repair_tool_arguments,
+        tool_mapping::ToolMapping, usage::estimate_missing_usage,
+    },
+    error::{AppError, UpstreamFailure},
     models::{
         claude::{
             AnthropicStreamEvent, ClaudeMessagesRequest, ClaudeStreamMessage, ClaudeStreamUsage,
             ContentBlock, ContentBlockDelta, ContentBlockStart, ContentBlockStop, Delta,
             FinishReason, MessageDelta, MessageDeltaInfo, MessageStart, MessageStop,
+            find_stop_sequence,
+        },
+        openai::{
+            OpenAIDelta, OpenAIRequest, OpenAIStreamChoice, OpenAIStreamChunk, OpenAIStreamToolCall,
         },
-        openai::{OpenAIDelta, OpenAIStreamChoice, OpenAIStreamChunk, OpenAIStreamToolCall},
         shared::{
-            ActiveState, NextState, StreamState, decide_after_reasoning, decide_after_text,
-            decide_after_tool, decide_next_state,
+            ActiveState, NextState, StreamState, ToolCallState, decide_after_reasoning,
+            decide_after_text, decide_after_tool, decide_next_state,
         },
     },
+    retry::{ChunkStream, Reconnect, retry_uncommitted_stream},
     state::AppState,
+    telemetry::{self, RequestMetrics},
 };
 
 pub fn emit_event(event_type: &str, data: &impl serde::Serialize) -> Bytes {
@@ -35,12 +45,31 @@ pub fn emit_event(event_type: &str, data: &impl serde::Serialize) -> Bytes {
     Bytes::from(format!("event: {event_type}\ndata: {data_str}\n\n"))
 }
 
+pub fn emit_error(error: &AppError) -> Bytes {
+    emit_event("error", &error.to_error_body())
+}
+
+pub fn parse_stream_error(data: &str, parse_error: &serde_json::Error) -> AppError {
+    serde_json::from_str::<Value>(data)
+        .ok()
+        .as_ref()
+        .and_then(UpstreamFailure::from_stream_payload)
+        .map_or_else(
+            || {
+                AppError::StreamError(format!(
+                    "Failed to parse stream chunk: {parse_error}, data: {data}"
+                ))
+            },
+            AppError::UpstreamError,
+        )
+}
+
 pub fn emit_ping() -> Bytes {
     debug!("Emitting ping event");
     emit_event("ping", &json!({"type": "ping"}))
 }
 
-pub fn emit_initial_events(state: &StreamState) -> Vec<AnthropicStreamEvent> {
+pub fn emit_initial_events(state: &StreamState, model: &str) -> Vec<AnthropicStreamEvent> {
     debug!("Emitting initial events for message: {}", state.message_id);
     vec![AnthropicStreamEvent::MessageStart(MessageStart {
         message: ClaudeStreamMessage {
@@ -48,17 +77,28 @@ pub fn emit_initial_events(state: &StreamState) -> Vec<AnthropicStreamEvent> {
             message_type: "message".to_string(),
             role: "assistant".to_string(),
             content: vec![],
-            model: state.model.clone(),
+            model: model.to_string(),
             stop_reason: None,
             stop_sequence: None,
             usage: ClaudeStreamUsage {
-                input_tokens: 0,
-                output_tokens: 0,
+                input_tokens: state.usage_data.input,
+                output_tokens: state.usage_data.output,
             },
         },
     })]
 }
 
+// keeps the upstream completion id so a message can be found in the upstream logs
+fn upstream_message_id(id: &str) -> Option<String> {
+    if id.is_empty() {
+        None
+    } else if id.starts_with("msg_") {
+        Some(id.to_string())
+    } else {
+        Some(format!("msg_{id}"))
+    }
+}
+
 #[must_use]
 pub fn emit_final_events(
     current_state: ActiveState,
@@ -77,24 +117,33 @@ pub fn emit_final_events(
         && let Some(tool_call) = context.tool_calls.get(&tool_index)
         && let Some(content_index) = tool_call.content_index
     {
-        events.push(AnthropicStreamEvent::ContentBlockStop(ContentBlockStop {
-            index: content_index,
-        }));
+        events.extend(close_tool_block(tool_call, content_index));
     }
 
-    let stop_reason = if context.tool_calls.is_empty() {
-        FinishReason(Some(finish_reason))
-            .to_anthropic_stop_reason()
-            .to_string()
-    } else {
+    let stop_sequence = (context.tool_calls.is_empty() && finish_reason == "stop")
+        .then(|| {
+            find_stop_sequence(
+                &context.stop_sequences,
+                context.matched_stop.as_deref(),
+                &context.text_tail,
+            )
+        })
+        .flatten();
+    let stop_reason = if !context.tool_calls.is_empty() {
         // workaround cases where upstream apis sends another ContentBlockStop with end_turn after tool use
         // but the client expects a tool_use stop reason
         "tool_use".to_string()
+    } else if stop_sequence.is_some() {
+        "stop_sequence".to_string()
+    } else {
+        FinishReason(Some(finish_reason))
+            .to_anthropic_stop_reason()
+            .to_string()
     };
     events.push(AnthropicStreamEvent::MessageDelta(MessageDelta {
         delta: MessageDeltaInfo {
             stop_reason,
-            stop_sequence: None,
+            stop_sequence,
         },
         usage: context.usage_data.clone(),
     }));
@@ -104,23 +153,27 @@ pub fn emit_final_events(
     events
 }
 
-fn generate_unique_id(prefix: &str, index: u32) -> String {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis();
-    format!("{prefix}_{timestamp}_{index}")
-}
-
+// upstreams spread usage over several chunks, a chunk without a field keeps the earlier value
 pub fn update_usage_from_chunk(chunk: &OpenAIStreamChunk, state: &mut StreamState) {
-    let usage = &chunk.usage;
-    state.usage_data.input = usage.prompt_tokens;
-    state.usage_data.output = usage.completion_tokens;
-    if let Some(details) = &usage.prompt_tokens_details
-        && let Some(cached_tokens) = details.cached_tokens
-    {
-        state.usage_data.cache_read_input = Some(cached_tokens);
+    let reported = chunk.usage.to_claude_usage();
+    let usage = &mut state.usage_data;
+    if reported.cache_read_input.is_some() {
+        usage.cache_read_input = reported.cache_read_input;
+    }
+    if reported.cache_creation_input.is_some() {
+        usage.cache_creation_input = reported.cache_creation_input;
+    }
+    if reported.output != 0 {
+        usage.output = reported.output;
     }
+    if chunk.usage.prompt_tokens != 0 {
+        state.prompt_tokens = chunk.usage.prompt_tokens;
+    }
+    let cached = usage
+        .cache_read_input
+        .unwrap_or(0)
+        .saturating_add(usage.cache_creation_input.unwrap_or(0));
+    usage.input = state.prompt_tokens.saturating_sub(cached);
     if state.usage_data.input != 0 || state.usage_data.output != 0 {
         debug!(
             "Updated usage data: input={}, output={}",
@@ -140,6 +193,24 @@ pub fn handle_tool_calls_delta(
         .collect()
 }
 
+// arguments are only known to be complete once the block ends, so they go out in one piece,
+// repaired if the model produced broken json
+fn close_tool_block(tool_call: &ToolCallState, content_index: u32) -> Vec<AnthropicStreamEvent> {
+    let mut events = Vec::new();
+    if !tool_call.arguments.trim().is_empty() {
+        events.push(AnthropicStreamEvent::ContentBlockDelta(ContentBlockDelta {
+            index: content_index,
+            delta: Delta::InputJson {
+                partial_json: repair_tool_arguments(&tool_call.arguments),
+            },
+        }));
+    }
+    events.push(AnthropicStreamEvent::ContentBlockStop(ContentBlockStop {
+        index: content_index,
+    }));
+    events
+}
+
 fn process_tool_call_delta(
     tool_call: &OpenAIStreamToolCall,
     state: &mut StreamState,
@@ -161,18 +232,13 @@ fn process_tool_call_delta(
     let starts_new_tool_use = has_function_name && !entry_has_name;
 
     if starts_new_tool_use {
-        if let Some(stop_event) = state
+        if let Some(active_tool) = state
             .tool_index
             .filter(|&active_index| active_index != tool_call.index)
             .and_then(|active_index| state.tool_calls.get(&active_index))
-            .and_then(|active_tool| active_tool.content_index)
-            .map(|content_index| {
-                AnthropicStreamEvent::ContentBlockStop(ContentBlockStop {
-                    index: content_index,
-                })
-            })
+            && let Some(content_index) = active_tool.content_index
         {
-            events.push(stop_event);
+            events.extend(close_tool_block(active_tool, content_index));
         }
         state.tool_index = Some(tool_call.index);
     }
@@ -185,39 +251,27 @@ fn process_tool_call_delta(
 
     if let Some(function) = &tool_call.function {
         if let (Some(name), None) = (function.name.as_ref(), entry.name.as_ref()) {
+            let name = state.tool_mapping.client_name(name);
             entry.name = Some(name.clone());
 
             let content_index = state.next_content_index;
             state.next_content_index += 1;
             entry.content_index = Some(content_index);
 
-            let tool_use_id = entry
-                .id
-                .clone()
-                .unwrap_or_else(|| generate_unique_id("call", tool_call.index));
+            let tool_use_id = state.tool_mapping.client_id(entry.id.as_deref());
 
             events.push(AnthropicStreamEvent::ContentBlockStart(ContentBlockStart {
                 index: content_index,
                 content_block: ContentBlock::ToolUse {
                     id: tool_use_id,
-                    name: name.clone(),
+                    name,
                     input: Value::Object(Map::new()),
                 },
             }));
         }
 
-        if let Some(arguments) = &function.arguments
-            && !arguments.is_empty()
-        {
+        if let Some(arguments) = &function.arguments {
             entry.arguments.push_str(arguments);
-            if let Some(content_index) = entry.content_index {
-                events.push(AnthropicStreamEvent::ContentBlockDelta(ContentBlockDelta {
-                    index: content_index,
-                    delta: Delta::InputJson {
-                        partial_json: arguments.clone(),
-                    },
-                }));
-            }
         }
     }
 
@@ -225,18 +279,12 @@ fn process_tool_call_delta(
 }
 
 #[must_use]
-pub fn convert_openai_stream_to_anthropic(
-    response: reqwest::Response,
-    model: &str,
-    adapter: &RequestAdapter,
-    request: &ClaudeMessagesRequest,
-    state: &AppState,
-) -> Pin<Box<dyn Stream<Item = Result<Bytes, AppError>> + Send>> {
+pub fn parse_openai_sse_chunks(response: reqwest::Response) -> ChunkStream {
     let byte_stream = response.bytes_stream().map_err(AppError::from);
 
     let stream_reader = StreamReader::new(byte_stream);
 
-    let chunk_stream =
+    Box::pin(
         FramedRead::new(stream_reader, SseDecoder::<String>::new()).filter_map(|frame| async {
             match frame {
                 Ok(Frame::Event(event)) => {
@@ -246,22 +294,26 @@ pub fn convert_openai_stream_to_anthropic(
                     }
                     match serde_json::from_str::<OpenAIStreamChunk>(data) {
                         Ok(chunk) => Some(Ok(chunk)),
-                        Err(e) => Some(Err(AppError::StreamError(format!(
-                            "Failed to parse stream chunk: {e}, data: {data}"
-                        )))),
+                        Err(e) => Some(Err(parse_stream_error(data, &e))),
                     }
                 }
                 Ok(_) => None,
                 Err(e) => Some(Err(e.into())),
             }
-        });
-
-    let adapted_chunk_stream = adapter.adapt_chunk_stream(Box::pin(chunk_stream), request);
-    let event_stream = chunks_to_events(model, adapted_chunk_stream, state.idle_connection_timeout);
+        }),
+    )
+}
 
+#[must_use]
+pub fn events_to_sse(
+    event_stream: Pin<Box<dyn Stream<Item = Result<AnthropicStreamEvent, AppError>> + Send>>,
+    ping_interval: Duration,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, AppError>> + Send>> {
     Box::pin(stream! {
-        let mut stream = Box::pin(event_stream);
-        let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        let mut stream = event_stream;
+        // the first tick of a plain interval fires at once, a deferred message_start must come first
+        let mut ping_interval =
+            tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
 
         loop {
             tokio::select! {
@@ -273,11 +325,7 @@ pub fn convert_openai_stream_to_anthropic(
                                 yield Ok(emit_event(event_type, &data));
                             }
                             Err(e) => {
-                                let error_event = json!({
-                                    "type": "error",
-                                    "error": { "type": "api_error", "message": e.to_string() }
-                                });
-                                yield Ok(emit_event("error", &error_event));
+                                yield Ok(emit_error(&e));
                                 break;
                             }
                         }
@@ -293,12 +341,84 @@ pub fn convert_openai_stream_to_anthropic(
     })
 }
 
+#[derive(Clone, Default)]
+pub struct StreamOptions {
+    pub reconnect: Option<Reconnect>,
+    pub metrics: Option<RequestMetrics>,
+    // the converted request, to estimate usage from when the upstream doesn't report it
+    pub usage_fallback: Option<OpenAIRequest>,
+}
+
+#[must_use]
+pub fn convert_openai_stream_to_anthropic(
+    response: reqwest::Response,
+    model: &str,
+    adapter: &RequestAdapter,
+    request: &ClaudeMessagesRequest,
+    state: &AppState,
+    options: StreamOptions,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, AppError>> + Send>> {
+    events_to_sse(
+        convert_openai_stream_to_events(response, model, adapter, request, state, options),
+        Duration::from_secs(state.config.ping_interval),
+    )
+}
+
+#[must_use]
+pub fn convert_openai_stream_to_events(
+    response: reqwest::Response,
+    model: &str,
+    adapter: &RequestAdapter,
+    request: &ClaudeMessagesRequest,
+    state: &AppState,
+    options: StreamOptions,
+) -> Pin<Box<dyn Stream<Item = Result<AnthropicStreamEvent, AppError>> + Send>> {
+    let chunk_stream = adapter.chunk_stream(response, request);
+    let chunk_stream = match options.reconnect {
+        Some(reconnect) => {
+            let adapter = adapter.clone();
+            let request = request.clone();
+            retry_uncommitted_stream(
+                chunk_stream,
+                reconnect,
+                move |response| adapter.chunk_stream(response, &request),
+                state.config.retry_policy(),
+            )
+        }
+        None => chunk_stream,
+    };
+    let stop_sequences = request.stop_sequences.clone().unwrap_or_default();
+    let tool_mapping = ToolMapping::new(adapter.tool_naming(request), request);
+    let event_stream = chunks_to_events(
+        model,
+        chunk_stream,
+        stop_sequences,
+        tool_mapping,
+        adapter.defers_message_start(request),
+        &state.config,
+    );
+    let event_stream = match options.usage_fallback {
+        Some(openai_request) => estimate_missing_usage(event_stream, openai_request),
+        None => event_stream,
+    };
+    match options.metrics {
+        Some(metrics) => metrics.observe_events(event_stream),
+        None => event_stream,
+    }
+}
+
 #[must_use]
 pub fn chunks_to_events(
     model: &str,
     mut chunk_stream: Pin<Box<dyn Stream<Item = Result<OpenAIStreamChunk, AppError>> + Send>>,
-    idle_timeout_secs: u64,
+    stop_sequences: Vec<String>,
+    tool_mapping: ToolMapping,
+    defer_message_start: bool,
+    config: &Config,
 ) -> Pin<Box<dyn Stream<Item = Result<AnthropicStreamEvent, AppError>> + Send>> {
+    let idle_timeout_secs = config.idle_connection_timeout;
+    let message_start_deadline = Duration::from_millis(config.message_start_deadline_ms);
+    let model_label = telemetry::model_label(model, config);
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
@@ -308,31 +428,57 @@ pub fn chunks_to_events(
     let mut state = StreamState {
         model: model.to_string(),
         message_id,
+        think_parser: ThinkTagParser::new(config.enable_reasoning_reentry),
+        stop_sequences,
+        tool_mapping,
         ..Default::default()
     };
 
     Box::pin(stream! {
-        for initial_event in emit_initial_events(&state) {
+        let mut stream = chunk_stream.as_mut();
+        // pings keep the client connection alive while message_start waits for the first chunk,
+        // past the deadline it goes out with the values known up front
+        let mut first = None;
+        let mut start_model = state.model.clone();
+        if defer_message_start
+            && let Ok(next) = tokio::time::timeout(message_start_deadline, stream.next()).await
+        {
+            if let Some(Ok(chunk)) = &next {
+                update_usage_from_chunk(chunk, &mut state);
+                if let Some(message_id) = upstream_message_id(&chunk.id) {
+                    state.message_id = message_id;
+                }
+                if !chunk.model.is_empty() {
+                    start_model.clone_from(&chunk.model);
+                }
+            }
+            first = Some(next);
+        }
+        for initial_event in emit_initial_events(&state, &start_model) {
             yield Ok(initial_event);
         }
 
-        let mut stream = chunk_stream.as_mut();
         let mut last_state = state.state;
         let timeout_duration = Duration::from_secs(idle_timeout_secs);
         loop {
-            let chunk_result = match tokio::time::timeout(timeout_duration, stream.next()).await {
-                Ok(Some(result)) => result,
-                Ok(None) => break,
-                Err(_) => {
-                    error!("Stream timed out after {idle_timeout_secs} seconds of inactivity");
-                    break;
-                }
+            let next = if let Some(next) = first.take() {
+                next
+            } else if let Ok(next) = tokio::time::timeout(timeout_duration, stream.next()).await {
+                next
+            } else {
+                error!("Stream timed out after {idle_timeout_secs} seconds of inactivity");
+                telemetry::record_stream_idle_timeout(&model_label);
+                yield Err(AppError::StreamIdleTimeout(idle_timeout_secs));
+                return;
+            };
+            let Some(chunk_result) = next else {
+                break;
             };
             let chunk = match chunk_result {
                 Ok(chunk_value) => chunk_value,
                 Err(error) => {
                     yield Err(error);
-                    break;
+                    return;
                 }
             };
 
@@ -350,14 +496,14 @@ pub fn chunks_to_events(
             last_state = state.state;
         }
 
+        // without a finish_reason the answer was cut off, closing it normally would pass it off as complete
         if let Some(reason) = &state.finish_reason {
             for event in emit_final_events(last_state, &state, reason) {
                 yield Ok(event);
             }
         } else {
-            for event in emit_final_events(last_state, &state, "stop_sequence") {
-                yield Ok(event);
-            }
+            error!("Upstream stream ended without a finish_reason");
+            yield Err(AppError::StreamTruncated);
         }
     })
 }
@@ -379,6 +525,7 @@ impl StreamState {
                             ..choice.delta.clone()
                         },
                         finish_reason: choice.finish_reason.clone(),
+                        stop_reason: choice.stop_reason.clone(),
                     }
                 }
                 None => choice.clone(),
@@ -386,12 +533,39 @@ impl StreamState {
         } else {
             choice.clone()
         };
+        if let Some(matched) = choice.stop_reason.as_ref().and_then(Value::as_str) {
+            self.matched_stop = Some(matched.to_string());
+        }
         let current_state = std::mem::take(&mut self.state);
         let (new_state, events) = Self::transition(current_state, &prepared_choice, self);
         self.state = new_state;
+        self.track_text_tail(&events);
         events
     }
 
+    // only as much emitted text as the longest stop sequence is kept for tail matching
+    fn track_text_tail(&mut self, events: &[AnthropicStreamEvent]) {
+        let Some(max_len) = self.stop_sequences.iter().map(String::len).max() else {
+            return;
+        };
+        for event in events {
+            if let AnthropicStreamEvent::ContentBlockDelta(ContentBlockDelta {
+                delta: Delta::Text { text },
+                ..
+            }) = event
+            {
+                self.text_tail.push_str(text);
+            }
+        }
+        if self.text_tail.len() > max_len {
+            let mut cut = self.text_tail.len() - max_len;
+            while !self.text_tail.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            self.text_tail.drain(..cut);
+        }
+    }
+
     fn transition(
         current_state: ActiveState,
         choice: &OpenAIStreamChoice,
@@ -474,6 +648,7 @@ impl StreamState {
                     ..choice.delta.clone()
                 },
                 finish_reason: choice.finish_reason.clone(),
+                stop_reason: choice.stop_reason.clone(),
             };
             Self::handle_thinking(&cleaned_choice, index, context)
         } else {
@@ -532,10 +707,11 @@ impl StreamState {
                 let remaining_choice = OpenAIStreamChoice {
                     index: choice.index,
                     delta: OpenAIDelta {
-                        content: Some(remaining_text.to_string()),
+                        content: Some(remaining_text.clone()),
                         ..choice.delta.clone()
                     },
                     finish_reason: choice.finish_reason.clone(),
+                    stop_reason: choice.stop_reason.clone(),
                 };
                 let (new_state, remaining_events) = Self::handle_idle(&remaining_choice, context);
                 events.extend(remaining_events);
@@ -687,10 +863,11 @@ impl StreamState {
             let remaining_choice = OpenAIStreamChoice {
                 index: choice.index,
                 delta: OpenAIDelta {
-                    content: Some(remaining_chunk.to_string()),
+                    content: Some(remaining_chunk.clone()),
                     ..choice.delta.clone()
                 },
                 finish_reason: choice.finish_reason.clone(),
+                stop_reason: choice.stop_reason.clone(),
             };
 
             let (new_state, remaining_events) = Self::handle_idle(&remaining_choice, context);
@@ -737,9 +914,7 @@ impl StreamState {
                     && let Some(active_tool) = context.tool_calls.get_mut(&active_index)
                     && let Some(content_index) = active_tool.content_index.take()
                 {
-                    events.push(AnthropicStreamEvent::ContentBlockStop(ContentBlockStop {
-                        index: content_index,
-                    }));
+                    events.extend(close_tool_block(active_tool, content_index));
                 }
                 context.tool_index = None;
                 (ActiveState::Tool, events)
@@ -16,17 +16,17 @@ pub struct ThinkTagParser {
 
 impl Default for ThinkTagParser {
     fn default() -> Self {
-        Self::new()
+        Self::new(false)
     }
 }
 
 impl ThinkTagParser {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(reentry_enabled: bool) -> Self {
         Self {
             state: ThinkParserState::Passthrough,
             buffer: String::new(),
-            reentry_disabled: std::env::var("ENABLE_REASONING_REENTRY").is_err(),
+            reentry_disabled: !reentry_enabled,
         }
     }
 
@@ -0,0 +1,62 @@
+use llm_json::{RepairOptions, loads};
+use serde_json::{Map, Value};
+use tracing::{debug, warn};
+
+// raw control characters are only invalid inside strings, everywhere else they are whitespace
+fn escape_control_characters(arguments: &str) -> String {
+    let mut escaped = String::with_capacity(arguments.len());
+    let mut in_string = false;
+    let mut after_backslash = false;
+    for c in arguments.chars() {
+        match c {
+            _ if after_backslash => {
+                after_backslash = false;
+                escaped.push(c);
+            }
+            '\\' if in_string => {
+                after_backslash = true;
+                escaped.push(c);
+            }
+            '"' => {
+                in_string = !in_string;
+                escaped.push(c);
+            }
+            '\n' if in_string => escaped.push_str("\\n"),
+            '\r' if in_string => escaped.push_str("\\r"),
+            '\t' if in_string => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// models break tool call json in small ways, trailing commas, raw newlines inside strings,
+// or a tail cut off at max_tokens, the client rejects all of them as invalid tool input
+#[must_use]
+pub fn parse_tool_arguments(arguments: &str) -> Value {
+    if arguments.trim().is_empty() {
+        return Value::Object(Map::new());
+    }
+    if let Ok(input @ Value::Object(_)) = serde_json::from_str(arguments) {
+        return input;
+    }
+    let escaped = escape_control_characters(arguments);
+    if let Ok(input @ Value::Object(_)) = serde_json::from_str(&escaped) {
+        return input;
+    }
+    if let Ok(input @ Value::Object(_)) = loads(&escaped, &RepairOptions::default()) {
+        debug!("Repaired tool call arguments: {arguments}");
+        return input;
+    }
+    warn!("Dropping tool call arguments that are not a json object: {arguments}");
+    Value::Object(Map::new())
+}
+
+// valid arguments pass through untouched, anything else is replaced by its repaired form
+#[must_use]
+pub fn repair_tool_arguments(arguments: &str) -> String {
+    if matches!(serde_json::from_str(arguments), Ok(Value::Object(_))) {
+        return arguments.to_string();
+    }
+    parse_tool_arguments(arguments).to_string()
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, fmt::Write};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::{Rng, distr::Alphanumeric};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    conversion::request::Request,
+    directives::models::ToolNaming,
+    models::claude::{ClaudeContent, ClaudeToolDefinition},
+};
+
+// openai rejects function names outside ^[a-zA-Z0-9_-]{1,64}$, most other upstreams copied the rule
+const MAX_TOOL_NAME_LEN: usize = 64;
+const MISTRAL_ID_LEN: usize = 9;
+const CLIENT_ID_PREFIX: &str = "toolu_";
+// generated and anthropic ids are alphanumeric after toolu_, so the underscore can't collide
+const ENCODED_ID_PREFIX: &str = "toolu_enc_";
+
+fn digest(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_TOOL_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// the digest of the full name keeps names that only differ after the cut apart
+fn upstream_tool_name(name: &str) -> String {
+    if is_valid_name(name) {
+        return name.to_string();
+    }
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_TOOL_NAME_LEN - 9)
+        .collect();
+    format!("{sanitized}_{}", &digest(name)[..8])
+}
+
+#[must_use]
+pub fn generate_tool_use_id() -> String {
+    let suffix: String = rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect();
+    format!("{CLIENT_ID_PREFIX}{suffix}")
+}
+
+// client ids carry the upstream id so the next request can hand it back unchanged
+fn decode_client_id(id: &str) -> Option<String> {
+    let encoded = id.strip_prefix(ENCODED_ID_PREFIX)?;
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(encoded).ok()?).ok()?;
+    (!decoded.is_empty() && decoded.chars().all(|c| c.is_ascii_graphic())).then_some(decoded)
+}
+
+// rewrites tool names and tool call ids to what the upstream accepts and maps the replies back
+#[derive(Debug, Clone, Default)]
+pub struct ToolMapping {
+    naming: ToolNaming,
+    // upstream name to client name, only for the names that had to change
+    names: HashMap<String, String>,
+}
+
+impl ToolMapping {
+    #[must_use]
+    pub fn new(naming: ToolNaming, request: &Request) -> Self {
+        let mut mapping = Self {
+            naming,
+            names: HashMap::new(),
+        };
+        if naming == ToolNaming::Verbatim {
+            return mapping;
+        }
+        let declared = request
+            .tools
+            .iter()
+            .flatten()
+            .map(ClaudeToolDefinition::name);
+        let chosen = request
+            .tool_choice
+            .iter()
+            .filter_map(|choice| choice.name.as_deref());
+        let called = request
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                ClaudeContent::Array(blocks) => Some(blocks),
+                ClaudeContent::Text(_) => None,
+            })
+            .flatten()
+            .filter(|block| block.block_type == "tool_use")
+            .filter_map(|block| block.name.as_deref());
+        for name in declared.chain(chosen).chain(called) {
+            let upstream = upstream_tool_name(name);
+            if upstream != name {
+                mapping.names.insert(upstream, name.to_string());
+            }
+        }
+        mapping
+    }
+
+    #[must_use]
+    pub fn upstream_name(&self, name: &str) -> String {
+        if self.naming == ToolNaming::Verbatim {
+            name.to_string()
+        } else {
+            upstream_tool_name(name)
+        }
+    }
+
+    #[must_use]
+    pub fn client_name(&self, name: &str) -> String {
+        self.names
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    // a pure function of the client id, so a tool_use and its tool_result always stay paired
+    #[must_use]
+    pub fn upstream_id(&self, id: &str) -> String {
+        if self.naming == ToolNaming::Verbatim {
+            return id.to_string();
+        }
+        let upstream = decode_client_id(id).unwrap_or_else(|| id.to_string());
+        match self.naming {
+            ToolNaming::Call => match upstream.strip_prefix(CLIENT_ID_PREFIX) {
+                Some(rest) => format!("call_{rest}"),
+                None => upstream,
+            },
+            ToolNaming::Mistral
+                if upstream.len() != MISTRAL_ID_LEN
+                    || !upstream.chars().all(|c| c.is_ascii_alphanumeric()) =>
+            {
+                digest(id)[..MISTRAL_ID_LEN].to_string()
+            }
+            _ => upstream,
+        }
+    }
+
+    #[must_use]
+    pub fn client_id(&self, id: Option<&str>) -> String {
+        match id.filter(|id| !id.is_empty()) {
+            Some(id) if self.naming == ToolNaming::Verbatim => id.to_string(),
+            Some(id) => format!("{ENCODED_ID_PREFIX}{}", URL_SAFE_NO_PAD.encode(id)),
+            None => generate_tool_use_id(),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use async_stream::stream;
+use futures_util::StreamExt;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    models::{
+        claude::{AnthropicStreamEvent, ContentBlock, Delta},
+        openai::OpenAIRequest,
+        shared::MessageDeltaUsage,
+    },
+    telemetry::EventStream,
+    tokenizer::{count_request_tokens, count_text_tokens},
+};
+
+pub const USAGE_HEADER: &str = "x-ant-compat-usage";
+
+// fills the counters the upstream left at zero, true when anything had to be estimated
+fn fill_missing_usage(
+    usage: &mut MessageDeltaUsage,
+    request: &OpenAIRequest,
+    output: &str,
+) -> bool {
+    let mut estimated = false;
+    let cached = usage
+        .cache_read_input
+        .unwrap_or(0)
+        .saturating_add(usage.cache_creation_input.unwrap_or(0));
+    if usage.input == 0 && cached == 0 {
+        usage.input = count_request_tokens(request);
+        estimated = true;
+    }
+    if usage.output == 0 && !output.is_empty() {
+        usage.output = count_text_tokens(output);
+        estimated = true;
+    }
+    if estimated {
+        usage.estimated = true;
+        debug!(
+            "Estimated usage locally: input={}, output={}",
+            usage.input, usage.output
+        );
+    }
+    estimated
+}
+
+// the prompt is only tokenized when the upstream didn't report usage, most of them do
+#[must_use]
+pub fn estimate_missing_usage(events: EventStream, request: OpenAIRequest) -> EventStream {
+    Box::pin(stream! {
+        let mut events = events;
+        let mut output = String::new();
+        while let Some(mut event) = events.next().await {
+            match &mut event {
+                Ok(AnthropicStreamEvent::ContentBlockStart(start)) => {
+                    if let ContentBlock::ToolUse { name, .. } = &start.content_block {
+                        output.push_str(name);
+                    }
+                }
+                Ok(AnthropicStreamEvent::ContentBlockDelta(delta)) => match &delta.delta {
+                    Delta::Text { text } => output.push_str(text),
+                    Delta::Thinking { thinking } => output.push_str(thinking),
+                    Delta::InputJson { partial_json } => output.push_str(partial_json),
+                    Delta::Signature { .. } => {}
+                },
+                Ok(AnthropicStreamEvent::MessageDelta(delta)) => {
+                    fill_missing_usage(&mut delta.usage, &request, &output);
+                }
+                _ => {}
+            }
+            yield event;
+        }
+    })
+}
+
+fn response_output(response: &Value) -> String {
+    response["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str().unwrap_or_default().to_string(),
+            Some("thinking") => block["thinking"].as_str().unwrap_or_default().to_string(),
+            Some("tool_use") => format!(
+                "{}{}",
+                block["name"].as_str().unwrap_or_default(),
+                block["input"]
+            ),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+// same fallback for a complete claude response, returns whether the usage is an estimate
+pub fn estimate_response_usage(response: &mut Value, request: &OpenAIRequest) -> bool {
+    let Ok(mut usage) = serde_json::from_value::<MessageDeltaUsage>(response["usage"].clone())
+    else {
+        return false;
+    };
+    // an aggregated stream was estimated on its way through, the header replaces the marker
+    if usage.estimated {
+        if let Some(usage) = response["usage"].as_object_mut() {
+            usage.remove("ant_compat_estimated");
+        }
+        return true;
+    }
+    let estimated = fill_missing_usage(&mut usage, request, &response_output(response));
+    if estimated {
+        response["usage"]["input_tokens"] = usage.input.into();
+        response["usage"]["output_tokens"] = usage.output.into();
+    }
+    estimated
+}
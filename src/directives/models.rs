@@ -23,6 +23,38 @@ pub struct ResponsesSettings {
     pub reasoning_summary: Option<String>,
 }
 
+// how earlier assistant thinking blocks are replayed to the upstream model
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkingHistory {
+    #[default]
+    Drop,
+    ReasoningContent,
+    Reasoning,
+    Inline,
+}
+
+// how pdf document blocks are sent upstream, plain text documents are always inlined
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentMode {
+    #[default]
+    Text,
+    File,
+}
+
+// how tool names and tool call ids are rewritten for upstreams with stricter rules than anthropic.
+// every mode but verbatim also shortens names to 64 characters
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolNaming {
+    #[default]
+    Upstream,
+    Call,
+    Mistral,
+    Verbatim,
+}
+
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct Settings {
     #[serde(default)]
@@ -38,6 +70,16 @@ pub struct Settings {
     #[serde(default)]
     pub enable_meowsings: Option<bool>,
     #[serde(default)]
+    pub thinking_history: Option<ThinkingHistory>,
+    #[serde(default)]
+    pub documents: Option<DocumentMode>,
+    #[serde(default)]
+    pub forward_cache_control: Option<bool>,
+    #[serde(default)]
+    pub tool_naming: Option<ToolNaming>,
+    #[serde(default)]
+    pub defer_message_start: Option<bool>,
+    #[serde(default)]
     pub responses: Option<ResponsesSettings>,
 }
 
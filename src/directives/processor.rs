@@ -4,10 +4,12 @@ use regex::Regex;
 use tracing::debug;
 
 use crate::{
+    config::Config,
     lazy_regex,
     models::claude::{
         ClaudeContent, ClaudeContentBlock, ClaudeMessagesRequest, ClaudeSystem, ClaudeThinking,
     },
+    telemetry,
     utils::map_reasoning_effort_to_budget_tokens,
 };
 
@@ -22,8 +24,8 @@ const CLAUDE_MD_MARKER: &str = "<system-reminder>\nAs you answer the user's ques
 pub struct DirectiveProcessor;
 
 impl DirectiveProcessor {
-    pub fn process(request: &mut ClaudeMessagesRequest) -> Settings {
-        let directive = Self::find_directive(request);
+    pub fn process(request: &mut ClaudeMessagesRequest, config: &Config) -> Settings {
+        let directive = Self::find_directive(request, config.limit_directive_to_claudemd);
 
         if let Some(dir) = directive {
             let settings = Self::resolve_settings(request, &dir);
@@ -34,7 +36,10 @@ impl DirectiveProcessor {
         }
     }
 
-    fn find_directive(request: &mut ClaudeMessagesRequest) -> Option<ProxyDirective> {
+    fn find_directive(
+        request: &mut ClaudeMessagesRequest,
+        limit_enabled: bool,
+    ) -> Option<ProxyDirective> {
         if let Some(system_prompt) = &mut request.system
             && let Some(directive) = Self::extract_from_system(system_prompt)
         {
@@ -60,7 +65,6 @@ impl DirectiveProcessor {
                 };
                 // Enabling LIMIT_DIRECTIVE_TO_CLAUDEMD will break directives in zed rules (or other clients)
                 // for security, non-first user messages require the claudemd marker at the start
-                let limit_enabled = std::env::var("LIMIT_DIRECTIVE_TO_CLAUDEMD").is_ok();
                 let should_extract = if user_index == 0 {
                     !limit_enabled || begins_with_marker
                 } else {
@@ -155,6 +159,7 @@ impl DirectiveProcessor {
                 Ok(dir) => Some(dir),
                 Err(e) => {
                     debug!("Failed to parse directive JSON: {e}");
+                    telemetry::record_directive_parse_failure();
                     None
                 }
             };
@@ -214,6 +219,21 @@ impl DirectiveProcessor {
         if incoming.enable_meowsings.is_some() {
             accumulated.enable_meowsings = incoming.enable_meowsings;
         }
+        if incoming.thinking_history.is_some() {
+            accumulated.thinking_history = incoming.thinking_history;
+        }
+        if incoming.documents.is_some() {
+            accumulated.documents = incoming.documents;
+        }
+        if incoming.forward_cache_control.is_some() {
+            accumulated.forward_cache_control = incoming.forward_cache_control;
+        }
+        if incoming.tool_naming.is_some() {
+            accumulated.tool_naming = incoming.tool_naming;
+        }
+        if incoming.defer_message_start.is_some() {
+            accumulated.defer_message_start = incoming.defer_message_start;
+        }
         if incoming.responses.is_some() {
             accumulated.responses =
                 Self::merge_responses(accumulated.responses, incoming.responses.clone());
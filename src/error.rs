@@ -1,21 +1,141 @@
 use axum::{
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Json, Response},
 };
-use serde_json::json;
+use serde_json::{Value, json};
 use thiserror::Error;
-use tracing::error;
+use tracing::{error, warn};
+
+#[derive(Debug, Clone)]
+pub struct UpstreamFailure {
+    pub status: StatusCode,
+    pub message: String,
+    pub hint: Option<String>,
+    pub retry_after: Option<HeaderValue>,
+}
+
+impl UpstreamFailure {
+    #[must_use]
+    pub fn new(status: StatusCode, body: &str, retry_after: Option<HeaderValue>) -> Self {
+        let parsed = serde_json::from_str::<Value>(body).ok();
+        let (message, hint) = parsed
+            .as_ref()
+            .and_then(Self::extract_error_fields)
+            .unwrap_or_else(|| (body.trim().to_string(), None));
+        Self {
+            status,
+            message,
+            hint,
+            retry_after,
+        }
+    }
+
+    // mid-stream errors arrive as `data: {"error": {...}}` with a 200 status
+    #[must_use]
+    pub fn from_stream_payload(payload: &Value) -> Option<Self> {
+        let error = payload.get("error")?;
+        let (message, hint) = Self::extract_error_fields(payload)?;
+        let status = error
+            .get("code")
+            .and_then(Value::as_u64)
+            .and_then(|code| u16::try_from(code).ok())
+            .and_then(|code| StatusCode::from_u16(code).ok())
+            .unwrap_or(StatusCode::OK);
+        Some(Self {
+            status,
+            message,
+            hint,
+            retry_after: None,
+        })
+    }
+
+    // openai style `{"error": {"message", "type", "code"}}`, with fallbacks for gateways
+    // that return `{"error": "..."}` or a top level `message`/`detail`
+    fn extract_error_fields(payload: &Value) -> Option<(String, Option<String>)> {
+        let error = payload.get("error");
+        let message = error
+            .and_then(|e| e.get("message"))
+            .or_else(|| error.filter(|e| e.is_string()))
+            .or_else(|| payload.get("message"))
+            .or_else(|| payload.get("detail"))
+            .and_then(Value::as_str)?
+            .to_string();
+        let hint = error
+            .and_then(|e| e.get("type").or_else(|| e.get("code")))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        Some((message, hint))
+    }
+
+    fn classify_hint(hint: &str) -> &'static str {
+        let hint = hint.to_lowercase();
+        if hint.contains("rate_limit") || hint.contains("quota") {
+            "rate_limit_error"
+        } else if hint.contains("overloaded") || hint.contains("unavailable") {
+            "overloaded_error"
+        } else if hint.contains("auth") || hint.contains("api_key") {
+            "authentication_error"
+        } else if hint.contains("permission") || hint.contains("forbidden") {
+            "permission_error"
+        } else if hint.contains("not_found") {
+            "not_found_error"
+        } else if hint.contains("invalid") || hint.contains("context_length") {
+            "invalid_request_error"
+        } else {
+            "api_error"
+        }
+    }
+
+    #[must_use]
+    pub fn error_type(&self) -> &'static str {
+        match self.status.as_u16() {
+            400 | 422 => "invalid_request_error",
+            413 => "request_too_large",
+            401 => "authentication_error",
+            403 => "permission_error",
+            404 => "not_found_error",
+            429 => "rate_limit_error",
+            503 | 529 => "overloaded_error",
+            _ => self
+                .hint
+                .as_deref()
+                .map_or("api_error", Self::classify_hint),
+        }
+    }
+
+    #[must_use]
+    pub fn response_status(&self) -> StatusCode {
+        match self.error_type() {
+            "overloaded_error" => StatusCode::from_u16(529).unwrap_or(self.status),
+            "rate_limit_error" => StatusCode::TOO_MANY_REQUESTS,
+            _ if self.status.is_success() => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => self.status,
+        }
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum AppError {
-    #[error("Missing x-api-key header")]
+    #[error(
+        "Missing API key, send it as x-api-key, Authorization: Bearer or the api-key query parameter"
+    )]
     MissingApiKey,
-    #[error("Upstream error: {0} - {1}")]
-    UpstreamError(StatusCode, String),
+    #[error("Upstream error: {} - {}", .0.status, .0.message)]
+    UpstreamError(UpstreamFailure),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    RequestTooLarge(String),
+    #[error("Server is shutting down, please retry the request")]
+    ShuttingDown,
     #[error("Internal Server Error: {0}")]
     InternalServerError(String),
     #[error("Stream Error: {0}")]
     StreamError(String),
+    #[error("Upstream went idle for {0}s, the response is incomplete")]
+    StreamIdleTimeout(u64),
+    #[error("Upstream closed the stream before finishing the response")]
+    StreamTruncated,
     #[error("Request failed: {0}")]
     Reqwest(#[from] reqwest::Error),
     #[error("JSON serialization/deserialization failed: {0}")]
@@ -24,58 +144,114 @@ pub enum AppError {
     SseCodec(#[from] tokio_sse_codec::SseDecodeError),
 }
 
+impl AppError {
+    #[must_use]
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            AppError::MissingApiKey => "authentication_error",
+            AppError::InvalidRequest(_) => "invalid_request_error",
+            AppError::RequestTooLarge(_) => "request_too_large",
+            AppError::ShuttingDown | AppError::StreamIdleTimeout(_) => "overloaded_error",
+            AppError::UpstreamError(failure) => failure.error_type(),
+            _ => "api_error",
+        }
+    }
+
+    // transient failures that are worth another attempt against the upstream
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AppError::Reqwest(error) => error.is_connect() || error.is_timeout() || error.is_body(),
+            AppError::SseCodec(_) => true,
+            AppError::UpstreamError(failure) => matches!(
+                failure.error_type(),
+                "rate_limit_error" | "overloaded_error" | "api_error"
+            ),
+            _ => false,
+        }
+    }
+
+    #[must_use]
+    pub fn client_message(&self) -> String {
+        match self {
+            AppError::UpstreamError(failure) => failure.message.clone(),
+            AppError::InvalidRequest(message)
+            | AppError::RequestTooLarge(message)
+            | AppError::InternalServerError(message)
+            | AppError::StreamError(message) => message.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn to_error_body(&self) -> Value {
+        json!({
+            "type": "error",
+            "error": {
+                "type": self.error_type(),
+                "message": self.client_message()
+            }
+        })
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_type, error_message) = match self {
-            AppError::MissingApiKey => (
-                StatusCode::UNAUTHORIZED,
-                "authentication_error",
-                "Missing x-api-key header".to_string(),
-            ),
-            AppError::UpstreamError(status, message) => (status, "api_error", message),
+        let body = Json(self.to_error_body());
+        let (status, retry_after) = match self {
+            AppError::MissingApiKey => (StatusCode::UNAUTHORIZED, None),
+            AppError::InvalidRequest(message) => {
+                warn!("Rejected request: {message}");
+                (StatusCode::BAD_REQUEST, None)
+            }
+            AppError::RequestTooLarge(message) => {
+                warn!("Rejected request: {message}");
+                (StatusCode::PAYLOAD_TOO_LARGE, None)
+            }
+            AppError::UpstreamError(failure) => {
+                error!(
+                    "Upstream Error: {} ({}) - {}",
+                    failure.status,
+                    failure.error_type(),
+                    failure.message
+                );
+                (failure.response_status(), failure.retry_after)
+            }
             AppError::Reqwest(err) => {
                 error!("Request Error: {err}");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "api_error",
-                    format!("Request failed: {err}"),
-                )
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
             }
             AppError::SerdeJson(err) => {
                 error!("Serde Error: {err}");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "api_error",
-                    format!("JSON serialization/deserialization failed: {err}"),
-                )
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
             }
             AppError::SseCodec(err) => {
                 error!("SSE Codec Error: {err}");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "api_error",
-                    format!("SSE codec error: {err}"),
-                )
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
             }
+            AppError::ShuttingDown => (
+                StatusCode::from_u16(529).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                None,
+            ),
             AppError::InternalServerError(message) => {
                 error!("Internal Server Error: {message}");
-                (StatusCode::INTERNAL_SERVER_ERROR, "api_error", message)
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
             }
             AppError::StreamError(message) => {
                 error!("Stream Error: {message}");
-                (StatusCode::INTERNAL_SERVER_ERROR, "api_error", message)
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
             }
-        };
-
-        let body = Json(json!({
-            "type": "error",
-            "error": {
-                "type": error_type,
-                "message": error_message
+            AppError::StreamIdleTimeout(_) | AppError::StreamTruncated => {
+                error!("Stream Error: {self}");
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
             }
-        }));
+        };
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(retry_after) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, retry_after);
+        }
+        response
     }
 }
 
@@ -0,0 +1,46 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+
+// like axum's Json, but rejections are reported as anthropic errors that include the json path
+pub struct AnthropicJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for AnthropicJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                AppError::RequestTooLarge(
+                    "Request body exceeds the maximum allowed size".to_string(),
+                )
+            } else {
+                AppError::InvalidRequest(rejection.body_text())
+            }
+        })?;
+        parse_json_body(&bytes).map(AnthropicJson)
+    }
+}
+
+fn parse_json_body<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, AppError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(bytes);
+    serde_path_to_error::deserialize(deserializer).map_err(|error| {
+        let path = error.path().to_string();
+        let inner = error.into_inner();
+        // serde reports the root as "." and unknown positions (syntax errors) as "?"
+        if path == "." || path == "?" {
+            AppError::InvalidRequest(format!("Invalid request body: {inner}"))
+        } else {
+            AppError::InvalidRequest(format!("Invalid request body at {path}: {inner}"))
+        }
+    })
+}
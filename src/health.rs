@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::{config::ReadinessProbe, state::AppState};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub reachable: bool,
+    pub error: Option<String>,
+    pub checked_at: Instant,
+}
+
+// shared between requests so a burst of readiness checks results in a single upstream probe
+#[derive(Debug, Default)]
+pub struct UpstreamProbe {
+    last: Mutex<Option<ProbeResult>>,
+}
+
+impl UpstreamProbe {
+    pub async fn check(&self, state: &AppState) -> ProbeResult {
+        let mut last = self.last.lock().await;
+        let cache_for = Duration::from_secs(state.config.readiness_cache_secs);
+        if let Some(result) = last.as_ref()
+            && result.checked_at.elapsed() < cache_for
+        {
+            return result.clone();
+        }
+        let result = probe_upstream(state).await;
+        if let Some(error) = &result.error {
+            warn!("Upstream readiness probe failed: {error}");
+        }
+        *last = Some(result.clone());
+        result
+    }
+}
+
+async fn probe_upstream(state: &AppState) -> ProbeResult {
+    let config = &state.config;
+    let request = match config.readiness_probe {
+        ReadinessProbe::Off => None,
+        // any http answer means the upstream is reachable, even a 404 or 401
+        ReadinessProbe::Head => Some(state.http_client.head(&config.openai_base_url)),
+        ReadinessProbe::Models => Some(
+            state
+                .http_client
+                .get(format!("{}/models", config.openai_base_url))
+                .bearer_auth(config.readiness_probe_key.as_deref().unwrap_or_default()),
+        ),
+    };
+    let error = match request {
+        None => None,
+        Some(request) => match request.timeout(PROBE_TIMEOUT).send().await {
+            Ok(response)
+                if config.readiness_probe == ReadinessProbe::Models
+                    && !response.status().is_success() =>
+            {
+                Some(format!("upstream answered {}", response.status()))
+            }
+            Ok(_) => None,
+            Err(e) => Some(e.to_string()),
+        },
+    };
+    ProbeResult {
+        reachable: error.is_none(),
+        error,
+        checked_at: Instant::now(),
+    }
+}
+
+fn base_status(state: &AppState) -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+        "active_streams": state.streams.active_streams(),
+    })
+}
+
+#[must_use]
+pub fn liveness(state: &AppState) -> Value {
+    let mut body = base_status(state);
+    body["status"] = json!("ok");
+    body
+}
+
+pub async fn readiness(state: &AppState) -> (bool, Value) {
+    let mut body = base_status(state);
+    let ready = if state.config.readiness_probe == ReadinessProbe::Off {
+        true
+    } else {
+        let result = state.upstream_probe.check(state).await;
+        body["upstream"] = json!({
+            "probe": state.config.readiness_probe,
+            "reachable": result.reachable,
+            "checked_secs_ago": result.checked_at.elapsed().as_secs(),
+            "error": result.error,
+        });
+        result.reachable
+    };
+    body["status"] = json!(if ready { "ready" } else { "not_ready" });
+    (ready, body)
+}
@@ -0,0 +1,34 @@
+use crate::{
+    AppState,
+    health::{liveness, readiness},
+};
+use axum::{
+    extract::State,
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Json, Response},
+};
+
+pub async fn handle_healthz(State(state): State<AppState>) -> Response {
+    Json(liveness(&state)).into_response()
+}
+
+pub async fn handle_metrics(State(state): State<AppState>) -> Response {
+    match state.metrics.as_ref() {
+        Some(handle) => (
+            [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn handle_readyz(State(state): State<AppState>) -> Response {
+    let (ready, body) = readiness(&state).await;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
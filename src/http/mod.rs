@@ -1,3 +1,7 @@
-pub use self::routes::handle_messages;
+pub use self::{
+    health::{handle_healthz, handle_metrics, handle_readyz},
+    routes::{handle_count_tokens, handle_messages, handle_models},
+};
 
+pub mod health;
 pub mod routes;
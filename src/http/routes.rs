@@ -1,20 +1,37 @@
 use crate::{
     AppState,
     adapters::RequestAdapter,
-    conversion::{convert_claude_to_openai, convert_openai_to_claude},
-    directives::processor::DirectiveProcessor,
-    error::AppError,
-    models::{claude::ClaudeMessagesRequest, openai::OpenAIRequest},
+    auth::{ApiKeyQuery, extract_api_key},
+    conversion::{
+        aggregate::aggregate_events,
+        convert_claude_to_openai, convert_openai_models_to_claude, convert_openai_to_claude,
+        document::extract_documents,
+        images::inline_image_urls,
+        stream::{StreamOptions, convert_openai_stream_to_events},
+        tool_mapping::ToolMapping,
+        usage::{USAGE_HEADER, estimate_response_usage},
+    },
+    directives::{models::DocumentMode, processor::DirectiveProcessor},
+    error::{AppError, UpstreamFailure},
+    extract::AnthropicJson,
+    models::{
+        claude::{ClaudeCountTokensRequest, ClaudeMessagesRequest, ClaudeModelListQuery},
+        openai::{OpenAIModelList, OpenAIRequest},
+        shared::MessageDeltaUsage,
+    },
+    retry::{Reconnect, RetryPolicy, parse_retry_after},
+    telemetry::{RequestMetrics, model_label, tenant_label},
+    tokenizer::count_request_tokens,
 };
 use axum::{
-    Json as JsonExtractor,
     body::Body,
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Json, Response},
 };
-use serde_json::Value;
-use tracing::{debug, info};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
 
 struct RequestContext {
     openai_request: OpenAIRequest,
@@ -23,13 +40,22 @@ struct RequestContext {
     adapter: RequestAdapter,
     claude_request: ClaudeMessagesRequest,
     is_streaming: bool,
+    // a non streaming client request that is streamed from the upstream and folded back together
+    aggregates_stream: bool,
+    metrics: RequestMetrics,
+    dropped_server_tools: Vec<String>,
 }
 
+const WARNING_HEADER: &str = "x-ant-compat-warning";
+
 async fn handle_non_streaming_response(
     response: reqwest::Response,
     target_model: String,
     adapter: &RequestAdapter,
     request: &ClaudeMessagesRequest,
+    openai_request: &OpenAIRequest,
+    state: &AppState,
+    metrics: &RequestMetrics,
 ) -> Result<Response, AppError> {
     info!("Handling as a non-streaming request");
     let response_json: Value = response.json().await?;
@@ -37,10 +63,70 @@ async fn handle_non_streaming_response(
     let normalized = adapter.normalize_non_stream_json(response_json, request);
     let adapted_json = adapter.adapt_non_stream_response(normalized, request);
 
-    let claude_response = convert_openai_to_claude(&adapted_json, &target_model);
+    let claude_response = convert_openai_to_claude(
+        &adapted_json,
+        &target_model,
+        request.stop_sequences.as_deref().unwrap_or_default(),
+        &ToolMapping::new(adapter.tool_naming(request), request),
+        &state.config,
+    );
+    Ok(finish_non_streaming_response(
+        claude_response,
+        openai_request,
+        state,
+        Some(metrics),
+    ))
+}
+
+// usage, outcome and first token are recorded by the observed event stream, like for a stream
+async fn handle_aggregated_response(
+    response: reqwest::Response,
+    state: &AppState,
+    context: &RequestContext,
+) -> Response {
+    info!("Aggregating the upstream stream into a single response");
+    let events = convert_openai_stream_to_events(
+        response,
+        &context.target_model,
+        &context.adapter,
+        &context.claude_request,
+        state,
+        stream_options(state, context),
+    );
+    match aggregate_events(state.streams.track_events(events)).await {
+        Ok(claude_response) => {
+            finish_non_streaming_response(claude_response, &context.openai_request, state, None)
+        }
+        // already counted by the observed stream, handle_messages would count it a second time
+        Err(error) => error.into_response(),
+    }
+}
+
+fn finish_non_streaming_response(
+    mut claude_response: Value,
+    openai_request: &OpenAIRequest,
+    state: &AppState,
+    metrics: Option<&RequestMetrics>,
+) -> Response {
+    let estimated = state.config.estimate_usage
+        && estimate_response_usage(&mut claude_response, openai_request);
+    if let Some(metrics) = metrics {
+        if let Ok(usage) =
+            serde_json::from_value::<MessageDeltaUsage>(claude_response["usage"].clone())
+        {
+            metrics.record_usage(&usage);
+        }
+        metrics.record_outcome("success");
+    }
     info!("Sending back converted Claude response");
     debug!("Claude response: {claude_response:?}");
-    Ok(Json(claude_response).into_response())
+    let mut response = Json(claude_response).into_response();
+    if estimated {
+        response
+            .headers_mut()
+            .insert(USAGE_HEADER, HeaderValue::from_static("estimated"));
+    }
+    response
 }
 
 fn handle_streaming_response(
@@ -49,10 +135,12 @@ fn handle_streaming_response(
     adapter: &RequestAdapter,
     request: &ClaudeMessagesRequest,
     state: &AppState,
+    options: StreamOptions,
 ) -> Result<Response, AppError> {
     info!("Handling as a streaming request");
-    let stream = adapter.build_anthropic_sse_stream(response, target_model, request, state);
-    let body = Body::from_stream(stream);
+    let stream =
+        adapter.build_anthropic_sse_stream(response, target_model, request, state, options);
+    let body = Body::from_stream(state.streams.track(stream));
     let response = Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "text/event-stream")
@@ -66,6 +154,62 @@ fn handle_streaming_response(
     Ok(response.into_response())
 }
 
+fn upstream_request(
+    state: &AppState,
+    api_key: &str,
+    url: &str,
+    body: &Value,
+) -> reqwest::RequestBuilder {
+    state
+        .http_client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {api_key}"))
+        .json(body)
+}
+
+async fn send_with_retry<F, Fut>(
+    policy: &RetryPolicy,
+    send: F,
+) -> Result<reqwest::Response, AppError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<reqwest::Response, reqwest::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        let outcome = send().await;
+        let retry_after = match &outcome {
+            Ok(response)
+                if attempt < policy.max_retries
+                    && RetryPolicy::is_retryable_status(response.status()) =>
+            {
+                parse_retry_after(response)
+            }
+            Err(error)
+                if attempt < policy.max_retries && (error.is_connect() || error.is_timeout()) =>
+            {
+                None
+            }
+            _ => return outcome.map_err(AppError::from),
+        };
+        let Some(delay) = policy.delay_for(attempt, retry_after) else {
+            return outcome.map_err(AppError::from);
+        };
+        let reason = match &outcome {
+            Ok(response) => response.status().to_string(),
+            Err(error) => error.to_string(),
+        };
+        attempt += 1;
+        warn!(
+            "Upstream request failed ({reason}), retrying in {}ms (attempt {attempt}/{})",
+            delay.as_millis(),
+            policy.max_retries
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
 async fn send_openai_request(
     state: &AppState,
     api_key: &str,
@@ -73,35 +217,89 @@ async fn send_openai_request(
     adapter: &RequestAdapter,
     claude_request: &ClaudeMessagesRequest,
 ) -> Result<reqwest::Response, AppError> {
-    let url = format!("{}{}", state.openai_base_url, adapter.endpoint_suffix());
+    let url = format!(
+        "{}{}",
+        state.config.openai_base_url,
+        adapter.endpoint_suffix()
+    );
     let body = adapter.build_request_body(openai_request, claude_request);
-    Ok(state
-        .http_client
-        .post(url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {api_key}"))
-        .json(&body)
-        .send()
-        .await?)
+    send_with_retry(&state.config.retry_policy(), || {
+        upstream_request(state, api_key, &url, &body).send()
+    })
+    .await
 }
 
-fn prepare_request_context(
+// a single fresh attempt, used by the stream retry once the first response was already accepted
+fn stream_reconnect(state: &AppState, context: &RequestContext) -> Reconnect {
+    let state = state.clone();
+    let api_key = context.api_key.clone();
+    let url = format!(
+        "{}{}",
+        state.config.openai_base_url,
+        context.adapter.endpoint_suffix()
+    );
+    let body = context
+        .adapter
+        .build_request_body(&context.openai_request, &context.claude_request);
+    Arc::new(move || {
+        let request = upstream_request(&state, &api_key, &url, &body);
+        Box::pin(async move { validate_upstream_response(request.send().await?).await })
+    })
+}
+
+fn stream_options(state: &AppState, context: &RequestContext) -> StreamOptions {
+    StreamOptions {
+        reconnect: Some(stream_reconnect(state, context)),
+        metrics: Some(context.metrics.clone()),
+        usage_fallback: state
+            .config
+            .estimate_usage
+            .then(|| context.openai_request.clone()),
+    }
+}
+
+async fn prepare_request_context(
     state: &AppState,
     headers: &HeaderMap,
+    key_query: &ApiKeyQuery,
     mut request: ClaudeMessagesRequest,
 ) -> Result<RequestContext, AppError> {
     info!("Preparing request for model: {}", request.model);
-    let settings = DirectiveProcessor::process(&mut request);
-    let api_key = extract_api_key(headers)?;
+    request.validate_sources()?;
+    let settings = DirectiveProcessor::process(&mut request, &state.config);
+    let api_key = request_api_key(state, headers, key_query)?;
 
-    let target_model = if request.model.to_lowercase().contains("haiku") {
-        state.default_haiku_model.clone()
-    } else {
-        request.model.clone()
-    };
+    let target_model = resolve_target_model(state, &request.model);
     let is_streaming = request.stream.unwrap_or(false);
-    let adapter = RequestAdapter::for_model(&target_model, &settings);
-    let openai_request = convert_claude_to_openai(request.clone(), &target_model, &adapter);
+    let aggregates_stream = !is_streaming && state.config.upstream_always_stream;
+    if aggregates_stream {
+        request.stream = Some(true);
+    }
+    let adapter = RequestAdapter::for_model(&target_model, &settings, &state.config);
+    if adapter.document_mode(&request) == DocumentMode::Text {
+        extract_documents(&request).await;
+    }
+    let mut openai_request = convert_claude_to_openai(request.clone(), &target_model, &adapter);
+    if state.config.inline_image_urls {
+        inline_image_urls(&mut openai_request.messages, &state.image_fetcher).await;
+    }
+    let tenant = state
+        .config
+        .metrics_tenant_label
+        .then(|| tenant_label(&api_key));
+    let metrics = RequestMetrics::new(
+        &model_label(&target_model, &state.config),
+        adapter.adapter_set(),
+        is_streaming,
+        tenant,
+    );
+    let dropped_server_tools = adapter.dropped_server_tools(&request);
+    if !dropped_server_tools.is_empty() {
+        warn!(
+            "Removing server tools the upstream can't run: {}",
+            dropped_server_tools.join(", ")
+        );
+    }
 
     Ok(RequestContext {
         openai_request,
@@ -110,9 +308,20 @@ fn prepare_request_context(
         adapter,
         claude_request: request,
         is_streaming,
+        aggregates_stream,
+        metrics,
+        dropped_server_tools,
     })
 }
 
+fn resolve_target_model(state: &AppState, model: &str) -> String {
+    if model.to_lowercase().contains("haiku") {
+        state.config.haiku_model.clone()
+    } else {
+        model.to_string()
+    }
+}
+
 async fn validate_upstream_response(
     response: reqwest::Response,
 ) -> Result<reqwest::Response, AppError> {
@@ -120,27 +329,59 @@ async fn validate_upstream_response(
         Ok(response)
     } else {
         let status = response.status();
+        let retry_after = response.headers().get(RETRY_AFTER).cloned();
         let error_text = response.text().await.map_err(|e| {
             AppError::InternalServerError(format!("Failed to read upstream error body: {e}"))
         })?;
-        Err(AppError::UpstreamError(status, error_text))
+        Err(AppError::UpstreamError(UpstreamFailure::new(
+            status,
+            &error_text,
+            retry_after,
+        )))
     }
 }
 
 pub async fn handle_messages(
     State(state): State<AppState>,
     headers: HeaderMap,
-    JsonExtractor(request): JsonExtractor<ClaudeMessagesRequest>,
+    Query(key_query): Query<ApiKeyQuery>,
+    AnthropicJson(request): AnthropicJson<ClaudeMessagesRequest>,
+) -> Result<Response, AppError> {
+    let context = prepare_request_context(&state, &headers, &key_query, request).await?;
+    let result = forward_messages(&state, &context).await;
+    if let Err(error) = &result {
+        context.metrics.record_outcome(error.error_type());
+    }
+    result.map(|response| with_server_tools_warning(response, &context.dropped_server_tools))
+}
+
+fn with_server_tools_warning(mut response: Response, dropped: &[String]) -> Response {
+    if dropped.is_empty() {
+        return response;
+    }
+    let warning = format!(
+        "server tools not supported by the upstream were removed: {}",
+        dropped.join(", ")
+    );
+    if let Ok(value) = HeaderValue::from_str(&warning) {
+        response.headers_mut().insert(WARNING_HEADER, value);
+    }
+    response
+}
+
+async fn forward_messages(
+    state: &AppState,
+    context: &RequestContext,
 ) -> Result<Response, AppError> {
-    let context = prepare_request_context(&state, &headers, request)?;
     let response = send_openai_request(
-        &state,
+        state,
         &context.api_key,
         &context.openai_request,
         &context.adapter,
         &context.claude_request,
     )
     .await?;
+    context.metrics.record_upstream_latency();
     let response = validate_upstream_response(response).await?;
 
     if context.is_streaming {
@@ -149,23 +390,68 @@ pub async fn handle_messages(
             &context.target_model,
             &context.adapter,
             &context.claude_request,
-            &state,
+            state,
+            stream_options(state, context),
         )
+    } else if context.aggregates_stream {
+        Ok(handle_aggregated_response(response, state, context).await)
     } else {
         handle_non_streaming_response(
             response,
-            context.target_model,
+            context.target_model.clone(),
             &context.adapter,
             &context.claude_request,
+            &context.openai_request,
+            state,
+            &context.metrics,
         )
         .await
     }
 }
 
-fn extract_api_key(headers: &HeaderMap) -> Result<String, AppError> {
-    headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .map(std::string::ToString::to_string)
+pub async fn handle_count_tokens(
+    State(state): State<AppState>,
+    AnthropicJson(request): AnthropicJson<ClaudeCountTokensRequest>,
+) -> Result<Response, AppError> {
+    let mut request = ClaudeMessagesRequest::from(request);
+    info!("Counting tokens for model: {}", request.model);
+    request.validate_sources()?;
+    let settings = DirectiveProcessor::process(&mut request, &state.config);
+    let target_model = resolve_target_model(&state, &request.model);
+    let adapter = RequestAdapter::for_model(&target_model, &settings, &state.config);
+    if adapter.document_mode(&request) == DocumentMode::Text {
+        extract_documents(&request).await;
+    }
+    let openai_request = convert_claude_to_openai(request, &target_model, &adapter);
+    let input_tokens = count_request_tokens(&openai_request);
+    debug!("Counted {input_tokens} input tokens for {target_model}");
+    Ok(Json(json!({ "input_tokens": input_tokens })).into_response())
+}
+
+pub async fn handle_models(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(key_query): Query<ApiKeyQuery>,
+    Query(query): Query<ClaudeModelListQuery>,
+) -> Result<Response, AppError> {
+    let api_key = request_api_key(&state, &headers, &key_query)?;
+    let response = state
+        .http_client
+        .get(format!("{}/models", state.config.openai_base_url))
+        .header("Authorization", format!("Bearer {api_key}"))
+        .send()
+        .await?;
+    let response = validate_upstream_response(response).await?;
+    let model_list: OpenAIModelList = response.json().await?;
+    debug!("Upstream returned {} models", model_list.data.len());
+    Ok(Json(convert_openai_models_to_claude(&model_list, &query)).into_response())
+}
+
+fn request_api_key(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: &ApiKeyQuery,
+) -> Result<String, AppError> {
+    extract_api_key(headers, query, state.config.api_key_prefix.as_deref())
         .ok_or(AppError::MissingApiKey)
 }
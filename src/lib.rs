@@ -1,8 +1,16 @@
 pub mod adapters;
+pub mod auth;
+pub mod config;
 pub mod conversion;
 pub mod directives;
 pub mod error;
+pub mod extract;
+pub mod health;
 pub mod logging;
 pub mod models;
+pub mod retry;
+pub mod shutdown;
 pub mod state;
+pub mod telemetry;
+pub mod tokenizer;
 pub mod utils;
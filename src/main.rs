@@ -1,73 +1,136 @@
 use anyhow::Result;
-use axum::{Router, routing::post};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    routing::{get, post},
+};
 use reqwest::Client;
 use std::env;
-use std::time::Duration;
-use tokio::net::TcpListener;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::{net::TcpListener, sync::watch};
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
-use tracing::info;
+use tracing::{info, warn};
 
 mod adapters;
+mod auth;
+mod config;
 mod conversion;
 mod directives;
 mod error;
+mod extract;
+mod health;
 mod http;
 mod logging;
 mod models;
+mod retry;
+mod shutdown;
 mod state;
+mod telemetry;
+mod tokenizer;
 mod utils;
 
-use http::handle_messages;
+use config::{CliArgs, Config};
+use conversion::images::ImageFetcher;
+use health::UpstreamProbe;
+use http::{
+    handle_count_tokens, handle_healthz, handle_messages, handle_metrics, handle_models,
+    handle_readyz,
+};
+use shutdown::{StreamTracker, shutdown_signal};
 use state::AppState;
 
+// time for the closed streams to flush their error event before connections are dropped
+const SHUTDOWN_MARGIN: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     logging::init();
 
-    let openai_base_url =
-        env::var("OPENAI_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:10152/v1".to_string());
-
-    let default_haiku_model =
-        env::var("HAIKU_MODEL").unwrap_or_else(|_| "openai/gpt-4.1-mini".to_string());
+    let cli = CliArgs::parse(env::args().skip(1))?;
+    let config = Config::load(&cli)?;
+    if cli.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
 
     let http_client = Client::builder()
-        .connect_timeout(Duration::from_secs(
-            env::var("CONNECTION_TIMEOUT")
-                .ok()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(10),
-        ))
-        .pool_idle_timeout(Duration::from_secs(
-            env::var("IDLE_CONNECTION_TIMEOUT")
-                .ok()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(60),
-        ))
+        .connect_timeout(Duration::from_secs(config.connection_timeout))
+        .pool_idle_timeout(Duration::from_secs(config.idle_connection_timeout))
         .build()?;
+    let image_fetcher = ImageFetcher::new(&config)?;
 
-    let idle_connection_timeout = env::var("IDLE_CONNECTION_TIMEOUT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(60);
+    let metrics_handle = telemetry::install_recorder();
+    if let Some(handle) = metrics_handle.clone() {
+        tokio::spawn(async move {
+            let mut upkeep = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                upkeep.tick().await;
+                handle.run_upkeep();
+            }
+        });
+    }
 
     let state = AppState {
-        openai_base_url,
-        default_haiku_model,
+        config: Arc::new(config),
         http_client,
-        idle_connection_timeout,
+        image_fetcher,
+        streams: StreamTracker::default(),
+        upstream_probe: Arc::new(UpstreamProbe::default()),
+        started_at: Instant::now(),
+        metrics: metrics_handle,
     };
 
     let app = Router::new()
         .route("/v1/messages", post(handle_messages))
+        .route("/v1/messages/count_tokens", post(handle_count_tokens))
+        .route("/v1/models", get(handle_models))
+        .route("/healthz", get(handle_healthz))
+        .route("/readyz", get(handle_readyz))
+        .route("/metrics", get(handle_metrics))
+        .layer(DefaultBodyLimit::max(state.config.max_request_body_bytes))
         .layer(ServiceBuilder::new().layer(CorsLayer::permissive()))
-        .with_state(state);
+        .with_state(state.clone());
 
-    let listen_addr = env::var("LISTEN").unwrap_or_else(|_| "0.0.0.0:33332".to_string());
+    let listen_addr = state.config.listen.clone();
     let listener = TcpListener::bind(&listen_addr).await?;
     info!("Server running on {listen_addr}");
 
-    axum::serve(listener, app).await?;
+    let streams = state.streams.clone();
+    let grace_period = Duration::from_secs(state.config.shutdown_grace_period);
+    let (draining, mut drain_started) = watch::channel(false);
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        draining.send_replace(true);
+        info!(
+            "Shutting down, draining {} active streams for up to {}s",
+            streams.active_streams(),
+            grace_period.as_secs()
+        );
+        tokio::spawn(async move {
+            tokio::time::sleep(grace_period).await;
+            if streams.active_streams() > 0 {
+                warn!(
+                    "Grace period elapsed, closing {} remaining streams",
+                    streams.active_streams()
+                );
+            }
+            streams.close_remaining();
+        });
+    });
+    // only streams are tracked, a slow non-streaming request would otherwise hold shutdown open
+    let hard_deadline = async move {
+        if drain_started.wait_for(|draining| *draining).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(grace_period + SHUTDOWN_MARGIN).await;
+    };
+    tokio::select! {
+        result = server => result?,
+        () = hard_deadline => warn!("Shutdown deadline reached, dropping remaining connections"),
+    }
+    info!("Server stopped");
 
     Ok(())
 }
@@ -1,11 +1,71 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaudeTool {
     pub name: String,
     pub description: Option<String>,
     pub input_schema: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<Value>,
+}
+
+// anthropic defined tools like web_search_20250305 have a versioned type instead of an input_schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeServerTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub options: Map<String, Value>,
+}
+
+impl ClaudeServerTool {
+    // the type without its date suffix, e.g. web_search for web_search_20250305
+    #[must_use]
+    pub fn kind(&self) -> &str {
+        match self.tool_type.rsplit_once('_') {
+            Some((kind, version)) if version.chars().all(|c| c.is_ascii_digit()) => kind,
+            _ => &self.tool_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ClaudeToolDefinition {
+    Custom(ClaudeTool),
+    Server(ClaudeServerTool),
+}
+
+// picked by hand instead of untagged, so a broken custom tool reports its missing or mistyped field
+impl<'de> Deserialize<'de> for ClaudeToolDefinition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let is_server = value.get("input_schema").is_none()
+            && value
+                .get("type")
+                .and_then(Value::as_str)
+                .is_some_and(|tool_type| tool_type != "custom");
+        if is_server {
+            ClaudeServerTool::deserialize(value).map(Self::Server)
+        } else {
+            ClaudeTool::deserialize(value).map(Self::Custom)
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+impl ClaudeToolDefinition {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Custom(tool) => &tool.name,
+            Self::Server(tool) => &tool.name,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,25 +75,58 @@ pub enum ClaudeContent {
     Array(Vec<ClaudeContentBlock>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ClaudeContentBlock {
     #[serde(rename = "type")]
     pub block_type: String,
     pub text: Option<String>,
-    pub source: Option<ImageSource>,
+    // the shape depends on the block type, see image_source and document_source
+    pub source: Option<Value>,
     pub id: Option<String>,
     pub name: Option<String>,
     pub input: Option<Value>,
     pub tool_use_id: Option<String>,
     pub content: Option<Value>,
+    pub thinking: Option<String>,
+    pub signature: Option<String>,
+    pub data: Option<String>,
+    pub title: Option<String>,
+    pub context: Option<String>,
+    pub cache_control: Option<Value>,
+}
+
+// routes reject malformed sources with validate_sources first, None only means no source here
+impl ClaudeContentBlock {
+    #[must_use]
+    pub fn image_source(&self) -> Option<ImageSource> {
+        self.source
+            .as_ref()
+            .and_then(|source| ImageSource::deserialize(source).ok())
+    }
+
+    #[must_use]
+    pub fn document_source(&self) -> Option<DocumentSource> {
+        self.source
+            .as_ref()
+            .and_then(|source| DocumentSource::deserialize(source).ok())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ImageSource {
-    #[serde(rename = "type")]
-    pub source_type: String,
-    pub media_type: String,
-    pub data: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+    File { file_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentSource {
+    Base64 { media_type: String, data: String },
+    Text { data: String },
+    Content { content: ClaudeContent },
+    Url { url: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +140,8 @@ pub struct ClaudeToolChoice {
     #[serde(rename = "type")]
     pub choice_type: String,
     pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_parallel_tool_use: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,7 +158,57 @@ pub enum ClaudeSystem {
     Array(Vec<ClaudeContentBlock>),
 }
 
+// the source shape depends on the block type, so it can only be checked once the block is parsed
+fn check_source(block_type: &str, source: &Value, path: &str) -> Result<(), AppError> {
+    let result = match block_type {
+        "image" => serde_path_to_error::deserialize::<_, ImageSource>(source).map(drop),
+        "document" => serde_path_to_error::deserialize::<_, DocumentSource>(source).map(drop),
+        _ => Ok(()),
+    };
+    result.map_err(|error| {
+        let path = match error.path().to_string().as_str() {
+            "." => format!("{path}.source"),
+            inner => format!("{path}.source.{inner}"),
+        };
+        AppError::InvalidRequest(format!(
+            "Invalid request body at {path}: {}",
+            error.into_inner()
+        ))
+    })
+}
+
 impl ClaudeMessagesRequest {
+    /// # Errors
+    ///
+    /// Returns `AppError::InvalidRequest` with the json path of the first image or document source,
+    /// including those inside tool results, that doesn't match its block type.
+    pub fn validate_sources(&self) -> Result<(), AppError> {
+        for (message_index, message) in self.messages.iter().enumerate() {
+            let ClaudeContent::Array(blocks) = &message.content else {
+                continue;
+            };
+            for (block_index, block) in blocks.iter().enumerate() {
+                let path = format!("messages[{message_index}].content[{block_index}]");
+                if let Some(source) = &block.source {
+                    check_source(&block.block_type, source, &path)?;
+                }
+                let nested = block.content.as_ref().and_then(Value::as_array);
+                for (nested_index, nested) in nested.into_iter().flatten().enumerate() {
+                    if let (Some(block_type), Some(source)) =
+                        (nested["type"].as_str(), nested.get("source"))
+                    {
+                        check_source(
+                            block_type,
+                            source,
+                            &format!("{path}.content[{nested_index}]"),
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     #[must_use]
     pub fn find_tool_name_by_id(&self, tool_use_id: &str) -> Option<String> {
         self.messages
@@ -100,11 +245,76 @@ pub struct ClaudeMessagesRequest {
     pub temperature: Option<f32>,
     pub top_p: Option<f32>,
     pub top_k: Option<u32>,
-    pub tools: Option<Vec<ClaudeTool>>,
+    pub tools: Option<Vec<ClaudeToolDefinition>>,
+    pub tool_choice: Option<ClaudeToolChoice>,
+    pub thinking: Option<ClaudeThinking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ClaudeMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClaudeMetadata {
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeCountTokensRequest {
+    pub model: String,
+    pub messages: Vec<ClaudeMessage>,
+    pub system: Option<ClaudeSystem>,
+    pub tools: Option<Vec<ClaudeToolDefinition>>,
     pub tool_choice: Option<ClaudeToolChoice>,
     pub thinking: Option<ClaudeThinking>,
 }
 
+impl From<ClaudeCountTokensRequest> for ClaudeMessagesRequest {
+    fn from(request: ClaudeCountTokensRequest) -> Self {
+        Self {
+            model: request.model,
+            messages: request.messages,
+            system: request.system,
+            // count_tokens has no max_tokens, it only affects the output budget anyway
+            max_tokens: 0,
+            stop_sequences: None,
+            stream: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: request.tools,
+            tool_choice: request.tool_choice,
+            thinking: request.thinking,
+            metadata: None,
+            service_tier: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClaudeModelListQuery {
+    pub before_id: Option<String>,
+    pub after_id: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeModelInfo {
+    #[serde(rename = "type")]
+    pub model_type: String,
+    pub id: String,
+    pub display_name: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeModelList {
+    pub data: Vec<ClaudeModelInfo>,
+    pub has_more: bool,
+    pub first_id: Option<String>,
+    pub last_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AnthropicStreamEvent {
@@ -240,8 +450,28 @@ impl FinishReason<'_> {
         match self.0 {
             Some("length") => "max_tokens",
             Some("tool_calls") => "tool_use",
-            Some("content_filter") => "stop_sequence",
+            Some("content_filter") => "refusal",
             _ => "end_turn",
         }
     }
 }
+
+// prefers the string the upstream reports, most backends leave the stop string out of the text
+// so the tail match only catches the ones that echo it
+#[must_use]
+pub fn find_stop_sequence(
+    stop_sequences: &[String],
+    upstream_match: Option<&str>,
+    text: &str,
+) -> Option<String> {
+    if let Some(matched) = upstream_match
+        && stop_sequences.iter().any(|sequence| sequence == matched)
+    {
+        return Some(matched.to_string());
+    }
+    stop_sequences
+        .iter()
+        .filter(|sequence| !sequence.is_empty())
+        .find(|sequence| text.ends_with(sequence.as_str()))
+        .cloned()
+}
@@ -22,8 +22,14 @@ pub enum OpenAIContent {
 pub struct OpenAIContentPart {
     #[serde(rename = "type")]
     pub part_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub image_url: Option<OpenAIImageUrl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<OpenAIFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +37,13 @@ pub struct OpenAIImageUrl {
     pub url: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    pub file_data: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct OpenAIMessage {
     pub role: String,
@@ -40,6 +53,10 @@ pub struct OpenAIMessage {
     pub tool_calls: Option<Vec<OpenAIToolCall>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,6 +78,8 @@ pub struct OpenAITool {
     #[serde(rename = "type")]
     pub tool_type: String,
     pub function: OpenAIToolFunction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -116,10 +135,18 @@ pub struct OpenAIRequest {
     pub tools: Option<Vec<OpenAITool>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<OpenAIToolChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_effort: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub stream_options: Option<StreamOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,11 +159,18 @@ pub struct OpenAIStreamChunk {
     pub usage: OpenAIUsage,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct OpenAIStreamChoice {
     pub index: u32,
     pub delta: OpenAIDelta,
     pub finish_reason: Option<String>,
+    // vllm sends the matched stop string (or a token id) as stop_reason, sglang as matched_stop
+    #[serde(
+        default,
+        alias = "matched_stop",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stop_reason: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -195,4 +229,48 @@ pub struct OpenAIUsage {
     pub completion_tokens: u32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub prompt_tokens_details: Option<super::shared::PromptTokensDetails>,
+    // litellm passes the anthropic counters through as is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+}
+
+impl OpenAIUsage {
+    // prompt_tokens includes cached tokens, anthropic reports them separately from input_tokens
+    #[must_use]
+    pub fn to_claude_usage(&self) -> super::shared::MessageDeltaUsage {
+        let details = self.prompt_tokens_details.as_ref();
+        let cache_read_input = details
+            .and_then(|details| details.cached_tokens)
+            .or(self.cache_read_input_tokens);
+        let cache_creation_input = details
+            .and_then(|details| details.cache_write_tokens)
+            .or(self.cache_creation_input_tokens);
+        let cached = cache_read_input
+            .unwrap_or(0)
+            .saturating_add(cache_creation_input.unwrap_or(0));
+        super::shared::MessageDeltaUsage {
+            input: self.prompt_tokens.saturating_sub(cached),
+            output: self.completion_tokens,
+            cache_read_input,
+            cache_creation_input,
+            estimated: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIModel {
+    pub id: String,
+    #[serde(default)]
+    pub created: Option<u64>,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIModelList {
+    #[serde(deserialize_with = "deserialize_null_as_default")]
+    pub data: Vec<OpenAIModel>,
 }
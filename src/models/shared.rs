@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    conversion::think_parser::ThinkTagParser,
+    conversion::{think_parser::ThinkTagParser, tool_mapping::ToolMapping},
     models::openai::{OpenAIStreamChoice, OpenAIStreamToolCall},
 };
 
@@ -18,11 +18,27 @@ pub struct MessageDeltaUsage {
         rename = "cache_read_input_tokens"
     )]
     pub cache_read_input: Option<u32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        rename = "cache_creation_input_tokens"
+    )]
+    pub cache_creation_input: Option<u32>,
+    // stream headers go out before the usage is known, so the final usage carries the marker
+    #[serde(
+        default,
+        skip_serializing_if = "std::ops::Not::not",
+        rename = "ant_compat_estimated"
+    )]
+    pub estimated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PromptTokensDetails {
     pub cached_tokens: Option<u32>,
+    // openrouter reports cache writes here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write_tokens: Option<u32>,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -58,10 +74,16 @@ pub struct StreamState {
     pub state: ActiveState,
     pub next_content_index: u32,
     pub usage_data: MessageDeltaUsage,
+    // the upstream count including cached tokens, usage_data.input is derived from it
+    pub prompt_tokens: u32,
     pub tool_calls: HashMap<u32, ToolCallState>,
     pub tool_index: Option<u32>,
     pub finish_reason: Option<String>,
     pub think_parser: ThinkTagParser,
+    pub stop_sequences: Vec<String>,
+    pub matched_stop: Option<String>,
+    pub text_tail: String,
+    pub tool_mapping: ToolMapping,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,125 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use async_stream::stream;
+use futures_util::{Stream, StreamExt};
+use reqwest::{Response, StatusCode, header::RETRY_AFTER};
+use tracing::warn;
+
+use crate::{error::AppError, models::openai::OpenAIStreamChunk};
+
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<OpenAIStreamChunk, AppError>> + Send>>;
+
+pub type Reconnect =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<Response, AppError>> + Send>> + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    #[must_use]
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+    }
+
+    // exponential backoff with jitter in the upper half, so concurrent clients don't retry in lockstep
+    #[must_use]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        let jitter_ms = u64::try_from(half.as_millis()).unwrap_or(u64::MAX);
+        half + Duration::from_millis(rand::random_range(0..=jitter_ms))
+    }
+
+    // None means the upstream asked us to wait longer than we are willing to, give up instead
+    #[must_use]
+    pub fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(delay) if delay > self.max_delay => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+}
+
+#[must_use]
+pub fn parse_retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+fn commits_content(chunk: &OpenAIStreamChunk) -> bool {
+    chunk.choices.iter().any(|choice| {
+        choice
+            .delta
+            .content
+            .as_deref()
+            .is_some_and(|c| !c.is_empty())
+            || choice.delta.get_reasoning().is_some_and(|r| !r.is_empty())
+            || choice.delta.tool_calls.is_some()
+    })
+}
+
+// message_start is already on the wire, but as long as no content block was emitted we can
+// transparently reconnect and the client only sees pings while we back off
+#[must_use]
+pub fn retry_uncommitted_stream(
+    initial: ChunkStream,
+    reconnect: Reconnect,
+    open_stream: impl Fn(Response) -> ChunkStream + Send + 'static,
+    policy: RetryPolicy,
+) -> ChunkStream {
+    Box::pin(stream! {
+        let mut chunk_stream = initial;
+        let mut committed = false;
+        let mut attempt = 0;
+        while let Some(chunk_result) = chunk_stream.next().await {
+            match chunk_result {
+                Ok(chunk) => {
+                    committed = committed || commits_content(&chunk);
+                    yield Ok(chunk);
+                }
+                Err(mut error) if !committed && attempt < policy.max_retries && error.is_retryable() => {
+                    let mut reconnected = None;
+                    while attempt < policy.max_retries && error.is_retryable() {
+                        let delay = policy.backoff(attempt);
+                        attempt += 1;
+                        warn!(
+                            "Upstream stream failed before any content ({error}), reconnecting in {}ms (attempt {attempt}/{})",
+                            delay.as_millis(),
+                            policy.max_retries
+                        );
+                        tokio::time::sleep(delay).await;
+                        match reconnect().await {
+                            Ok(response) => {
+                                reconnected = Some(open_stream(response));
+                                break;
+                            }
+                            Err(reconnect_error) => error = reconnect_error,
+                        }
+                    }
+                    if let Some(new_stream) = reconnected {
+                        chunk_stream = new_stream;
+                    } else {
+                        yield Err(error);
+                        break;
+                    }
+                }
+                Err(error) => {
+                    yield Err(error);
+                    break;
+                }
+            }
+        }
+    })
+}
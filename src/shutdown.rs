@@ -0,0 +1,136 @@
+use std::{
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use async_stream::stream;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+use crate::{conversion::stream::emit_error, error::AppError, telemetry::EventStream};
+
+pub type SseStream = Pin<Box<dyn Stream<Item = Result<Bytes, AppError>> + Send>>;
+
+struct ActiveGuard(Arc<AtomicUsize>);
+
+impl ActiveGuard {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        Self(active)
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// counts the sse streams in flight and lets shutdown cut off the ones still running at the deadline
+#[derive(Clone)]
+pub struct StreamTracker {
+    active: Arc<AtomicUsize>,
+    closing: Arc<watch::Sender<bool>>,
+}
+
+impl Default for StreamTracker {
+    fn default() -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            closing: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl StreamTracker {
+    #[must_use]
+    pub fn active_streams(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn close_remaining(&self) {
+        self.closing.send_replace(true);
+    }
+
+    #[must_use]
+    pub fn track(&self, inner: SseStream) -> SseStream {
+        self.guard(inner, || Ok(emit_error(&AppError::ShuttingDown)))
+    }
+
+    // an aggregated response is streamed from the upstream too, it fails with the same error
+    #[must_use]
+    pub fn track_events(&self, inner: EventStream) -> EventStream {
+        self.guard(inner, || Err(AppError::ShuttingDown))
+    }
+
+    fn guard<T: Send + 'static>(
+        &self,
+        inner: Pin<Box<dyn Stream<Item = Result<T, AppError>> + Send>>,
+        closed: fn() -> Result<T, AppError>,
+    ) -> Pin<Box<dyn Stream<Item = Result<T, AppError>> + Send>> {
+        let guard = ActiveGuard::new(self.active.clone());
+        let mut closing = self.closing.subscribe();
+        Box::pin(stream! {
+            let _guard = guard;
+            let mut inner = inner;
+            loop {
+                tokio::select! {
+                    item = inner.next() => {
+                        if let Some(item) = item {
+                            yield item;
+                        } else {
+                            break;
+                        }
+                    }
+                    () = wait_for_close(&mut closing) => {
+                        warn!("Shutdown deadline reached, closing stream");
+                        yield closed();
+                        break;
+                    }
+                }
+            }
+        })
+    }
+}
+
+// a dropped tracker can never close the stream, so treat it as never closing
+async fn wait_for_close(closing: &mut watch::Receiver<bool>) {
+    if closing.wait_for(|closing| *closing).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for ctrl-c: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+}
@@ -1,9 +1,20 @@
+use std::{sync::Arc, time::Instant};
+
+use metrics_exporter_prometheus::PrometheusHandle;
 use reqwest::Client;
 
+use crate::{
+    config::Config, conversion::images::ImageFetcher, health::UpstreamProbe,
+    shutdown::StreamTracker,
+};
+
 #[derive(Clone)]
 pub struct AppState {
-    pub openai_base_url: String,
-    pub default_haiku_model: String,
+    pub config: Arc<Config>,
     pub http_client: Client,
-    pub idle_connection_timeout: u64,
+    pub image_fetcher: ImageFetcher,
+    pub streams: StreamTracker,
+    pub upstream_probe: Arc<UpstreamProbe>,
+    pub started_at: Instant,
+    pub metrics: Option<PrometheusHandle>,
 }
@@ -0,0 +1,182 @@
+use std::{fmt::Write, pin::Pin, time::Instant};
+
+use async_stream::stream;
+use futures_util::{Stream, StreamExt};
+use metrics::{Label, counter, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::{
+    config::Config,
+    error::AppError,
+    models::{claude::AnthropicStreamEvent, shared::MessageDeltaUsage},
+};
+
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<AnthropicStreamEvent, AppError>> + Send>>;
+
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0,
+];
+
+#[must_use]
+pub fn install_recorder() -> Option<PrometheusHandle> {
+    let builder = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), &LATENCY_BUCKETS);
+    match builder.and_then(PrometheusBuilder::install_recorder) {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            error!("Failed to install metrics recorder, /metrics is disabled: {e}");
+            None
+        }
+    }
+}
+
+// only a short digest of the key is ever exported, never the key itself
+#[must_use]
+pub fn tenant_label(api_key: &str) -> String {
+    Sha256::digest(api_key.as_bytes())
+        .iter()
+        .take(6)
+        .fold(String::new(), |mut label, byte| {
+            let _ = write!(label, "{byte:02x}");
+            label
+        })
+}
+
+// model names come from the client, anything not configured is folded into one series
+#[must_use]
+pub fn model_label(model: &str, config: &Config) -> String {
+    if model == config.haiku_model || config.metrics_models.iter().any(|known| known == model) {
+        model.to_string()
+    } else {
+        "other".to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestMetrics {
+    model: String,
+    adapters: String,
+    stream: bool,
+    tenant: Option<String>,
+    started_at: Instant,
+}
+
+impl RequestMetrics {
+    #[must_use]
+    pub fn new(model: &str, adapters: String, stream: bool, tenant: Option<String>) -> Self {
+        Self {
+            model: model.to_string(),
+            adapters,
+            stream,
+            tenant,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn model_labels(&self) -> Vec<Label> {
+        let mut labels = vec![Label::new("model", self.model.clone())];
+        if let Some(tenant) = &self.tenant {
+            labels.push(Label::new("tenant", tenant.clone()));
+        }
+        labels
+    }
+
+    fn request_labels(&self) -> Vec<Label> {
+        let mut labels = self.model_labels();
+        labels.push(Label::new("adapters", self.adapters.clone()));
+        labels.push(Label::new("stream", self.stream.to_string()));
+        labels
+    }
+
+    pub fn record_outcome(&self, outcome: &'static str) {
+        let mut labels = self.request_labels();
+        labels.push(Label::new("outcome", outcome));
+        counter!("ant_compat_requests_total", labels).increment(1);
+    }
+
+    pub fn record_upstream_latency(&self) {
+        histogram!("ant_compat_upstream_latency_seconds", self.request_labels())
+            .record(self.started_at.elapsed().as_secs_f64());
+    }
+
+    pub fn record_first_token(&self) {
+        histogram!(
+            "ant_compat_time_to_first_token_seconds",
+            self.model_labels()
+        )
+        .record(self.started_at.elapsed().as_secs_f64());
+    }
+
+    pub fn record_usage(&self, usage: &MessageDeltaUsage) {
+        let counts = [
+            ("input", Some(usage.input)),
+            ("output", Some(usage.output)),
+            ("cache_read", usage.cache_read_input),
+            ("cache_creation", usage.cache_creation_input),
+        ];
+        for (kind, count) in counts {
+            if let Some(count) = count.filter(|count| *count > 0) {
+                let mut labels = self.model_labels();
+                labels.push(Label::new("kind", kind));
+                counter!("ant_compat_tokens_total", labels).increment(u64::from(count));
+            }
+        }
+    }
+
+    // records ttft, usage and the final outcome while passing the events through untouched
+    #[must_use]
+    pub fn observe_events(self, events: EventStream) -> EventStream {
+        Box::pin(stream! {
+            let mut outcome = OutcomeGuard { metrics: self, outcome: None };
+            let mut first_token_seen = false;
+            let mut events = events;
+            while let Some(event) = events.next().await {
+                match &event {
+                    Ok(AnthropicStreamEvent::ContentBlockDelta(_)) if !first_token_seen => {
+                        first_token_seen = true;
+                        outcome.metrics.record_first_token();
+                    }
+                    Ok(AnthropicStreamEvent::MessageDelta(delta)) => {
+                        outcome.metrics.record_usage(&delta.usage);
+                    }
+                    Err(error) => outcome.outcome = Some(error.error_type()),
+                    Ok(_) => {}
+                }
+                yield event;
+            }
+            outcome.outcome.get_or_insert("success");
+        })
+    }
+}
+
+// a stream dropped before it finished means the client went away
+struct OutcomeGuard {
+    metrics: RequestMetrics,
+    outcome: Option<&'static str>,
+}
+
+impl Drop for OutcomeGuard {
+    fn drop(&mut self) {
+        self.metrics
+            .record_outcome(self.outcome.unwrap_or("client_closed"));
+    }
+}
+
+pub fn record_stream_idle_timeout(model: &str) {
+    counter!("ant_compat_stream_idle_timeouts_total", "model" => model.to_string()).increment(1);
+}
+
+pub fn record_tool_simulation_finalize(format: &'static str, outcome: &'static str) {
+    counter!(
+        "ant_compat_tool_simulation_finalize_total",
+        "format" => format,
+        "outcome" => outcome
+    )
+    .increment(1);
+}
+
+pub fn record_directive_parse_failure() {
+    counter!("ant_compat_directive_parse_failures_total").increment(1);
+}
@@ -0,0 +1,115 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use memchr::memmem;
+use tiktoken_rs::{CoreBPE, o200k_base_singleton};
+
+use crate::models::openai::{OpenAIContent, OpenAIFile, OpenAIMessage, OpenAIRequest};
+
+// chat format overhead, see openai-cookbook "How to count tokens with tiktoken"
+const TOKENS_PER_MESSAGE: u32 = 3;
+const TOKENS_PER_REPLY: u32 = 3;
+// image dimensions are unknown without decoding, assume a ~1092x1092 image (w*h/750)
+const TOKENS_PER_IMAGE: u32 = 1600;
+// a pdf page is billed as its text plus an image of the page, anthropic quotes 1500-3000 per page
+const TOKENS_PER_PDF_PAGE: u32 = 2250;
+// binary files without a better estimate, roughly what the bpe does with base64 noise
+const BYTES_PER_FILE_TOKEN: usize = 4;
+
+fn bpe() -> &'static CoreBPE {
+    o200k_base_singleton()
+}
+
+#[must_use]
+pub fn count_text_tokens(text: &str) -> u32 {
+    if text.is_empty() {
+        return 0;
+    }
+    u32::try_from(bpe().encode_ordinary(text).len()).unwrap_or(u32::MAX)
+}
+
+// page objects are counted without parsing the pdf, /Type /Pages is the page tree and not a page
+fn count_pdf_pages(bytes: &[u8]) -> u32 {
+    let pages = [b"/Type /Page".as_slice(), b"/Type/Page".as_slice()]
+        .iter()
+        .map(|needle| {
+            memmem::find_iter(bytes, needle)
+                .filter(|start| bytes.get(start + needle.len()) != Some(&b's'))
+                .count()
+        })
+        .sum::<usize>()
+        .max(1);
+    u32::try_from(pages).unwrap_or(u32::MAX)
+}
+
+fn count_file_tokens(file: &OpenAIFile) -> u32 {
+    let Some((media_type, data)) = file
+        .file_data
+        .strip_prefix("data:")
+        .and_then(|url| url.split_once(";base64,"))
+    else {
+        return 0;
+    };
+    let Ok(bytes) = STANDARD.decode(data) else {
+        return 0;
+    };
+    if media_type == "application/pdf" {
+        return count_pdf_pages(&bytes).saturating_mul(TOKENS_PER_PDF_PAGE);
+    }
+    match std::str::from_utf8(&bytes) {
+        Ok(text) => count_text_tokens(text),
+        Err(_) => u32::try_from(bytes.len() / BYTES_PER_FILE_TOKEN).unwrap_or(u32::MAX),
+    }
+}
+
+fn count_content_tokens(content: &OpenAIContent) -> u32 {
+    match content {
+        OpenAIContent::Text(text) => count_text_tokens(text),
+        OpenAIContent::Array(parts) => parts
+            .iter()
+            .map(|part| {
+                let text_tokens = part.text.as_deref().map_or(0, count_text_tokens);
+                let image_tokens = if part.image_url.is_some() {
+                    TOKENS_PER_IMAGE
+                } else {
+                    0
+                };
+                let file_tokens = part.file.as_ref().map_or(0, count_file_tokens);
+                text_tokens + image_tokens + file_tokens
+            })
+            .sum(),
+    }
+}
+
+fn count_message_tokens(message: &OpenAIMessage) -> u32 {
+    let content_tokens = message.content.as_ref().map_or(0, count_content_tokens);
+    let tool_call_tokens: u32 = message
+        .tool_calls
+        .iter()
+        .flatten()
+        .map(|call| {
+            count_text_tokens(&call.function.name) + count_text_tokens(&call.function.arguments)
+        })
+        .sum();
+    // replayed thinking history is sent in one of the two fields
+    let reasoning_tokens: u32 = [&message.reasoning_content, &message.reasoning]
+        .into_iter()
+        .flatten()
+        .map(|reasoning| count_text_tokens(reasoning))
+        .sum();
+    TOKENS_PER_MESSAGE
+        + count_text_tokens(&message.role)
+        + content_tokens
+        + tool_call_tokens
+        + reasoning_tokens
+}
+
+#[must_use]
+pub fn count_request_tokens(request: &OpenAIRequest) -> u32 {
+    let message_tokens: u32 = request.messages.iter().map(count_message_tokens).sum();
+    let tool_tokens: u32 = request
+        .tools
+        .iter()
+        .flatten()
+        .map(|tool| count_text_tokens(&serde_json::to_string(&tool.function).unwrap_or_default()))
+        .sum();
+    message_tokens + tool_tokens + TOKENS_PER_REPLY
+}
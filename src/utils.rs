@@ -23,3 +23,30 @@ pub fn map_reasoning_effort_to_budget_tokens(reasoning_effort: &str) -> u32 {
         _ => 8192,
     }
 }
+
+// days-from-civil inverse, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+#[must_use]
+pub fn format_unix_timestamp_rfc3339(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let seconds_of_day = timestamp % 86_400;
+    let era_days = days + 719_468;
+    let era = era_days / 146_097;
+    let day_of_era = era_days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
@@ -18,6 +18,8 @@ fn dummy_request() -> ClaudeMessagesRequest {
         tools: None,
         tool_choice: None,
         thinking: None,
+        metadata: None,
+        service_tier: None,
     }
 }
 
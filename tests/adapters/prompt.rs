@@ -24,6 +24,8 @@ fn dummy_request() -> ClaudeMessagesRequest {
         tools: None,
         tool_choice: None,
         thinking: None,
+        metadata: None,
+        service_tier: None,
     }
 }
 
@@ -41,6 +43,8 @@ fn dummy_oai_request() -> ClaudeMessagesRequest {
         tools: None,
         tool_choice: None,
         thinking: None,
+        metadata: None,
+        service_tier: None,
     }
 }
 
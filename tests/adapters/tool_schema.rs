@@ -20,6 +20,8 @@ fn dummy_request() -> ClaudeMessagesRequest {
         tools: None,
         tool_choice: None,
         thinking: None,
+        metadata: None,
+        service_tier: None,
     }
 }
 
@@ -78,11 +80,13 @@ fn dummy_request() -> ClaudeMessagesRequest {
         "additionalProperties": false
     })
 )]
-fn test_gemini_schema_cleaning(#[case] name: &str, #[case] schema: serde_json::Value) {
+fn test_gemini_schema_cleaning(
+    #[case] name: &str,
+    #[case] schema: serde_json::Value,
+) -> serde_json::Result<()> {
     let adapter = GeminiToolSchemaAdapter;
     let request = dummy_request();
     let cleaned = adapter.adapt_tool_schema(&schema, &request);
-    let snapshot =
-        to_string_pretty(&cleaned).expect("Serialization of a JSON value should not fail");
-    assert_snapshot!(name, snapshot);
+    assert_snapshot!(name, to_string_pretty(&cleaned)?);
+    Ok(())
 }
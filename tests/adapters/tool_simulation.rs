@@ -2,12 +2,17 @@ use crate::helpers;
 use ant_compat::adapters::defaults::tool_simulation::parsing::parse_bracket_tool;
 use ant_compat::{
     adapters::RequestAdapter,
-    conversion::{request::convert_claude_to_openai, stream::convert_openai_stream_to_anthropic},
+    config::Config,
+    conversion::{
+        request::convert_claude_to_openai,
+        stream::{StreamOptions, convert_openai_stream_to_anthropic},
+    },
     directives::models::Settings,
     models::{
         claude::{
             AnthropicStreamEvent, ClaudeContent, ClaudeContentBlock, ClaudeMessage,
-            ClaudeMessagesRequest, ClaudeSystem, ClaudeTool, ClaudeToolChoice, ContentBlock,
+            ClaudeMessagesRequest, ClaudeSystem, ClaudeTool, ClaudeToolChoice,
+            ClaudeToolDefinition, ContentBlock,
         },
         openai::{OpenAIDelta, OpenAIStreamChoice, OpenAIStreamChunk, OpenAIUsage},
     },
@@ -41,6 +46,7 @@ fn text_content_block(text: &str) -> ClaudeContentBlock {
         input: None,
         tool_use_id: None,
         content: None,
+        ..ClaudeContentBlock::default()
     }
 }
 
@@ -54,6 +60,7 @@ fn tool_use_content_block(id: &str, name: &str, input: Value) -> ClaudeContentBl
         input: Some(input),
         tool_use_id: None,
         content: None,
+        ..ClaudeContentBlock::default()
     }
 }
 
@@ -67,6 +74,7 @@ fn tool_result_content_block(tool_use_id: &str, content: Value) -> ClaudeContent
         input: None,
         tool_use_id: Some(tool_use_id.to_string()),
         content: Some(content),
+        ..ClaudeContentBlock::default()
     }
 }
 
@@ -108,7 +116,7 @@ fn get_request(model: &str) -> ClaudeMessagesRequest {
         temperature: Some(0.5),
         top_p: Some(1.0),
         top_k: None,
-        tools: Some(vec![ClaudeTool {
+        tools: Some(vec![ClaudeToolDefinition::Custom(ClaudeTool {
             name: "get_weather".to_string(),
             description: Some("Get the current weather in a given location.".to_string()),
             input_schema: json!({
@@ -121,12 +129,16 @@ fn get_request(model: &str) -> ClaudeMessagesRequest {
                 },
                 "required": ["location"]
             }),
-        }]),
+            cache_control: None,
+        })]),
         tool_choice: Some(ClaudeToolChoice {
             choice_type: "auto".to_string(),
             name: None,
+            disable_parallel_tool_use: None,
         }),
         thinking: None,
+        metadata: None,
+        service_tier: None,
     }
 }
 
@@ -135,23 +147,46 @@ fn get_request(model: &str) -> ClaudeMessagesRequest {
 #[case("bracket_tools_conversion", "google/gemini-2.5-pro-bracket-tools")]
 fn verify_request_conversion(#[case] snapshot_name: &str, #[case] model: &str) {
     let request = get_request(model);
-    let adapter = RequestAdapter::for_model(model, &Settings::default());
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
     let result = convert_claude_to_openai(request, model, &adapter);
     insta::assert_debug_snapshot!(snapshot_name, &result);
 }
 
+fn gemini_chunk(id: usize, content: &str, finish_reason: Option<&str>) -> OpenAIStreamChunk {
+    OpenAIStreamChunk {
+        id: id.to_string(),
+        choices: vec![OpenAIStreamChoice {
+            index: 0,
+            delta: OpenAIDelta {
+                content: Some(content.to_string()),
+                ..Default::default()
+            },
+            finish_reason: finish_reason.map(ToString::to_string),
+            ..Default::default()
+        }],
+        model: "google/gemini-2.5-pro".to_string(),
+        usage: OpenAIUsage::default(),
+    }
+}
+
 async fn run_stream_conversion_test(
     model: &str,
     chunks: Vec<OpenAIStreamChunk>,
     snapshot_name: &str,
 ) {
     let request = get_request(model);
-    let mock_response = helpers::mock_response_from_chunks(chunks).await;
-    let adapter = RequestAdapter::for_model(model, &Settings::default());
+    let mock_response = helpers::mock_response_from_chunks(chunks);
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
     let mock_state = helpers::mock_app_state();
 
-    let anthropic_stream =
-        convert_openai_stream_to_anthropic(mock_response, model, &adapter, &request, &mock_state);
+    let anthropic_stream = convert_openai_stream_to_anthropic(
+        mock_response,
+        model,
+        &adapter,
+        &request,
+        &mock_state,
+        StreamOptions::default(),
+    );
 
     let mut events = helpers::collect_and_parse_stream(anthropic_stream).await;
     redact_ids(&mut events);
@@ -171,6 +206,7 @@ async fn verify_xml_tools_stream_conversion() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -184,6 +220,7 @@ async fn verify_xml_tools_stream_conversion() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -197,6 +234,7 @@ async fn verify_xml_tools_stream_conversion() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -212,6 +250,7 @@ async fn verify_xml_tools_stream_conversion() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -225,6 +264,7 @@ async fn verify_xml_tools_stream_conversion() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -235,6 +275,7 @@ async fn verify_xml_tools_stream_conversion() {
                 index: 0,
                 delta: OpenAIDelta::default(),
                 finish_reason: Some("stop".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -261,6 +302,7 @@ async fn verify_xml_tools_json_repair() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -274,6 +316,7 @@ async fn verify_xml_tools_json_repair() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -287,6 +330,7 @@ async fn verify_xml_tools_json_repair() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -305,6 +349,7 @@ async fn verify_xml_tools_json_repair() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -318,6 +363,7 @@ async fn verify_xml_tools_json_repair() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -344,6 +390,7 @@ async fn verify_xml_tools_fragmented_delimiter() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -359,6 +406,7 @@ async fn verify_xml_tools_fragmented_delimiter() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -376,112 +424,18 @@ async fn verify_xml_tools_fragmented_delimiter() {
 #[tokio::test]
 async fn verify_bracket_tools_stream_conversion() {
     let chunks = vec![
-        OpenAIStreamChunk {
-            id: "1".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("Thinking...\n".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "2".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("---TOOLS---\n".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "3".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("[tool(get_weather, location=\"San Francisco\")]\n".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "4".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some(
-                        "[tool(get_weather, location=\"\"\"Germany/FrankFurt\"\"\")]\n".to_string(),
-                    ),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "5".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("[tool(get_weather,".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "6".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("location=\"".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "7".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("\"\"Norway/Oslo\"\"\")]\n".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "8".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("---END_TOOLS---".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: Some("tool_calls".to_string()),
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
+        gemini_chunk(1, "Thinking...\n", None),
+        gemini_chunk(2, "---TOOLS---\n", None),
+        gemini_chunk(3, "[tool(get_weather, location=\"San Francisco\")]\n", None),
+        gemini_chunk(
+            4,
+            "[tool(get_weather, location=\"\"\"Germany/FrankFurt\"\"\")]\n",
+            None,
+        ),
+        gemini_chunk(5, "[tool(get_weather,", None),
+        gemini_chunk(6, "location=\"", None),
+        gemini_chunk(7, "\"\"Norway/Oslo\"\"\")]\n", None),
+        gemini_chunk(8, "---END_TOOLS---", Some("tool_calls")),
     ];
 
     run_stream_conversion_test(
@@ -504,6 +458,7 @@ async fn verify_bracket_tools_complex_parsing() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -519,6 +474,7 @@ async fn verify_bracket_tools_complex_parsing() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -534,6 +490,7 @@ async fn verify_bracket_tools_complex_parsing() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -543,10 +500,11 @@ async fn verify_bracket_tools_complex_parsing() {
             choices: vec![OpenAIStreamChoice {
                 index: 0,
                 delta: OpenAIDelta {
-                    content: Some(r#"[tool(NoArgs)]"#.to_string()),
+                    content: Some("[tool(NoArgs)]".to_string()),
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -560,6 +518,7 @@ async fn verify_bracket_tools_complex_parsing() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -573,6 +532,7 @@ async fn verify_bracket_tools_complex_parsing() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -599,6 +559,7 @@ async fn verify_bracket_tools_fragmented_delimiter() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -614,6 +575,7 @@ async fn verify_bracket_tools_fragmented_delimiter() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -640,6 +602,7 @@ async fn verify_bracket_tools_json_repair() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -667,6 +630,7 @@ async fn verify_bracket_tools_numeric_string_parsing() {
                 ..Default::default()
             },
             finish_reason: Some("tool_calls".to_string()),
+            ..Default::default()
         }],
         model: "google/gemini-2.5-pro".to_string(),
         usage: OpenAIUsage::default(),
@@ -693,6 +657,7 @@ async fn verify_bracket_tools_multibyte_character_parsing() {
                 ..Default::default()
             },
             finish_reason: Some("tool_calls".to_string()),
+            ..Default::default()
         }],
         model: "google/gemini-2.5-pro".to_string(),
         usage: OpenAIUsage::default(),
@@ -709,7 +674,12 @@ async fn verify_bracket_tools_multibyte_character_parsing() {
 #[rstest]
 fn parse_bracket_tool_name_with_hyphen_snapshot() {
     let slice = r#"[tool(mcp__mcp__searxng-search, query=\"rust\")]"#;
-    let call = parse_bracket_tool(slice).expect("parse");
+    let Some(call) = parse_bracket_tool(slice) else {
+        return insta::assert_snapshot!(
+            "bracket_tools_parse_hyphenated_name",
+            "unparsed tool call"
+        );
+    };
     insta::assert_debug_snapshot!("bracket_tools_parse_hyphenated_name", &call);
 }
 
@@ -725,6 +695,7 @@ async fn verify_bracket_tools_hyphen_tool_name_streaming() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -738,6 +709,7 @@ async fn verify_bracket_tools_hyphen_tool_name_streaming() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -751,6 +723,7 @@ async fn verify_bracket_tools_hyphen_tool_name_streaming() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -777,6 +750,7 @@ async fn verify_xml_tools_html_inside_json_array() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -790,6 +764,7 @@ async fn verify_xml_tools_html_inside_json_array() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -807,6 +782,7 @@ async fn verify_xml_tools_html_inside_json_array() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -820,6 +796,7 @@ async fn verify_xml_tools_html_inside_json_array() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -846,6 +823,7 @@ async fn verify_bracket_tools_array_before_string() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -862,6 +840,7 @@ async fn verify_bracket_tools_array_before_string() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -875,6 +854,7 @@ async fn verify_bracket_tools_array_before_string() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -888,6 +868,7 @@ async fn verify_bracket_tools_array_before_string() {
                     ..Default::default()
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                ..Default::default()
             }],
             model: "google/gemini-2.5-pro".to_string(),
             usage: OpenAIUsage::default(),
@@ -904,99 +885,23 @@ async fn verify_bracket_tools_array_before_string() {
 
 #[tokio::test]
 async fn verify_bracket_tools_multiedit_execution() {
-    let chunks = vec![
-        OpenAIStreamChunk {
-            id: "1".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("<end_cot>\n---TOOLS---\n".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "2".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("[tool(MultiEdit, file_path=\"\"\"".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "3".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("src/ue-bridge.go\"\"\", edits=\"\"\"[".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "4".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("{\"old_string\":\"foo\",\"new_string\":\"bar\"},".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "5".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("{\"old_string\":\"baz\",\"new_string\":\"qux\"}]".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "6".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("\"\"\")]".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: None,
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
-        OpenAIStreamChunk {
-            id: "7".to_string(),
-            choices: vec![OpenAIStreamChoice {
-                index: 0,
-                delta: OpenAIDelta {
-                    content: Some("---END_TOOLS---".to_string()),
-                    ..Default::default()
-                },
-                finish_reason: Some("tool_calls".to_string()),
-            }],
-            model: "google/gemini-2.5-pro".to_string(),
-            usage: OpenAIUsage::default(),
-        },
+    let contents = [
+        "<end_cot>\n---TOOLS---\n",
+        "[tool(MultiEdit, file_path=\"\"\"",
+        "src/ue-bridge.go\"\"\", edits=\"\"\"[",
+        "{\"old_string\":\"foo\",\"new_string\":\"bar\"},",
+        "{\"old_string\":\"baz\",\"new_string\":\"qux\"}]",
+        "\"\"\")]",
+        "---END_TOOLS---",
     ];
+    let chunks = contents
+        .iter()
+        .enumerate()
+        .map(|(index, content)| {
+            let finish_reason = (index + 1 == contents.len()).then_some("tool_calls");
+            gemini_chunk(index + 1, content, finish_reason)
+        })
+        .collect();
 
     run_stream_conversion_test(
         "google/gemini-2.5-pro-bracket-tools",
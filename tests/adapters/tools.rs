@@ -10,16 +10,16 @@ use std::{fs, path::Path};
 
 fn load_descriptions() -> Option<YamlOwned> {
     let path = Path::new("tests/fixtures/tool_descriptions.yaml");
-    if let Ok(content) = fs::read_to_string(path) {
-        if let Ok(mut docs) = YamlOwned::load_from_str(&content) {
-            return Some(docs.remove(0));
-        }
+    if let Ok(content) = fs::read_to_string(path)
+        && let Ok(mut docs) = YamlOwned::load_from_str(&content)
+    {
+        return Some(docs.remove(0));
     }
     None
 }
 
 fn dummy_request() -> Request {
-    let req = ClaudeMessagesRequest {
+    ClaudeMessagesRequest {
         model: "qwen3-coder-plus".to_string(),
         messages: vec![],
         system: None,
@@ -32,8 +32,9 @@ fn dummy_request() -> Request {
         tools: None,
         tool_choice: None,
         thinking: None,
-    };
-    req.into()
+        metadata: None,
+        service_tier: None,
+    }
 }
 
 struct TestGrepAdapter;
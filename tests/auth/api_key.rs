@@ -0,0 +1,47 @@
+use ant_compat::auth::{ApiKeyQuery, extract_api_key};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use rstest::rstest;
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            (
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            )
+        })
+        .collect()
+}
+
+fn query(api_key: Option<&str>) -> ApiKeyQuery {
+    ApiKeyQuery {
+        api_key: api_key.map(str::to_string),
+    }
+}
+
+#[rstest]
+#[case("x_api_key", headers(&[("x-api-key", "sk-header")]), query(None), None)]
+#[case("bearer", headers(&[("authorization", "Bearer sk-bearer")]), query(None), None)]
+#[case("bearer_lowercase_scheme", headers(&[("authorization", "bearer sk-bearer")]), query(None), None)]
+#[case("basic_auth_ignored", headers(&[("authorization", "Basic dXNlcjpwYXNz")]), query(None), None)]
+#[case("query_param", headers(&[]), query(Some("sk-query")), None)]
+#[case(
+    "x_api_key_wins",
+    headers(&[("x-api-key", "sk-header"), ("authorization", "Bearer sk-bearer")]),
+    query(Some("sk-query")),
+    None
+)]
+#[case("bearer_over_query", headers(&[("authorization", "Bearer sk-bearer")]), query(Some("sk-query")), None)]
+#[case("empty_header_falls_through", headers(&[("x-api-key", "")]), query(Some("sk-query")), None)]
+#[case("prefix_stripped", headers(&[("x-api-key", "antc-sk-upstream")]), query(None), Some("antc-"))]
+#[case("prefix_absent", headers(&[("x-api-key", "sk-upstream")]), query(None), Some("antc-"))]
+#[case("missing", headers(&[]), query(None), None)]
+fn verify_api_key_extraction(
+    #[case] name: &str,
+    #[case] headers: HeaderMap,
+    #[case] query: ApiKeyQuery,
+    #[case] prefix: Option<&str>,
+) {
+    insta::assert_debug_snapshot!(name, extract_api_key(&headers, &query, prefix));
+}
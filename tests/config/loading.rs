@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use ant_compat::config::{CliArgs, Config};
+use rstest::rstest;
+
+const CONFIG_FILE: &str = r#"
+openai_base_url = "https://gateway.internal/v1"
+haiku_model = "google/gemini-2.5-flash"
+upstream_max_retries = 4
+disable_default_adapters = true
+"#;
+
+fn load(file: Option<&str>, env: &[(&str, &str)]) -> Result<Config, String> {
+    Config::from_sources(
+        file.map(|contents| (PathBuf::from("ant-compat.toml"), contents)),
+        |name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value).to_string())
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[rstest]
+#[case("config_defaults", None, &[])]
+#[case("config_file", Some(CONFIG_FILE), &[])]
+#[case(
+    "config_env_overrides_file",
+    Some(CONFIG_FILE),
+    &[("HAIKU_MODEL", "openai/gpt-4.1-nano"), ("DISABLE_DEFAULT_ADAPTERS", "0"), ("ENABLE_REASONING_REENTRY", "yes")]
+)]
+#[case("config_empty_env_ignored", None, &[("UPSTREAM_MAX_RETRIES", ""), ("API_KEY_PREFIX", "")])]
+#[case("config_list_env", None, &[("METRICS_MODELS", "openai/gpt-5, qwen/qwen3-coder,")])]
+#[case("config_invalid_number", None, &[("CONNECTION_TIMEOUT", "ten")])]
+#[case("config_invalid_flag", None, &[("LIMIT_DIRECTIVE_TO_CLAUDEMD", "maybe")])]
+#[case("config_invalid_base_url", None, &[("OPENAI_BASE_URL", "127.0.0.1:10152/v1")])]
+#[case(
+    "config_invalid_retry_delays",
+    None,
+    &[("UPSTREAM_RETRY_BASE_DELAY_MS", "5000"), ("UPSTREAM_RETRY_MAX_DELAY_MS", "1000")]
+)]
+#[case("config_unknown_file_key", Some("listen_addr = \"0.0.0.0:1\""), &[])]
+fn verify_config_loading(
+    #[case] name: &str,
+    #[case] file: Option<&str>,
+    #[case] env: &[(&str, &str)],
+) {
+    insta::assert_debug_snapshot!(name, load(file, env));
+}
+
+#[rstest]
+#[case("cli_empty", &[])]
+#[case("cli_print_config", &["--print-config", "--config", "ant-compat.toml"])]
+#[case("cli_config_equals", &["--config=/etc/ant-compat.toml"])]
+#[case("cli_missing_path", &["--config"])]
+#[case("cli_unknown_argument", &["--verbose"])]
+fn verify_cli_args(#[case] name: &str, #[case] args: &[&str]) {
+    let parsed =
+        CliArgs::parse(args.iter().map(|arg| (*arg).to_string())).map_err(|e| e.to_string());
+    insta::assert_debug_snapshot!(name, parsed);
+}
+
+#[rstest]
+fn verify_print_config() {
+    let rendered = load(Some(CONFIG_FILE), &[("API_KEY_PREFIX", "antc-")])
+        .and_then(|config| config.to_toml().map_err(|e| e.to_string()));
+    insta::assert_snapshot!(rendered.unwrap_or_default());
+}
@@ -0,0 +1,148 @@
+use super::scenarios::{final_chunk, text_chunk, thinking_chunk, tool_chunk};
+use crate::helpers;
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::{
+        aggregate::aggregate_events,
+        non_stream::convert_openai_to_claude,
+        request::convert_claude_to_openai,
+        stream::{StreamOptions, convert_openai_stream_to_events},
+        tool_mapping::ToolMapping,
+        usage::estimate_response_usage,
+    },
+    directives::models::Settings,
+    models::{claude::ClaudeMessagesRequest, openai::OpenAIStreamChunk},
+};
+use rstest::rstest;
+use serde_json::{Value, json};
+
+// the folded stream has to look like what the non streaming path returns for the same answer
+#[rstest]
+#[case(
+    "aggregate_text",
+    vec![text_chunk("Hel"), text_chunk("lo"), final_chunk("stop")],
+    json!({ "role": "assistant", "content": "Hello" }),
+    "stop"
+)]
+#[case(
+    "aggregate_think_tags",
+    vec![text_chunk("<think>Plan</think>"), text_chunk("Done"), final_chunk("stop")],
+    json!({ "role": "assistant", "content": "<think>Plan</think>Done" }),
+    "stop"
+)]
+#[case(
+    "aggregate_reasoning_and_tool",
+    vec![
+        thinking_chunk("Need the file"),
+        tool_chunk(0, "call_1", "Read", r#"{"file_path":"#),
+        tool_chunk(0, "call_1", "Read", r#""README.md"}"#),
+        final_chunk("tool_calls"),
+    ],
+    json!({
+        "role": "assistant",
+        "reasoning_content": "Need the file",
+        "tool_calls": [{
+            "id": "call_1",
+            "type": "function",
+            "function": { "name": "Read", "arguments": r#"{"file_path":"README.md"}"# }
+        }]
+    }),
+    "tool_calls"
+)]
+#[tokio::test]
+async fn verify_aggregated_response(
+    #[case] name: &str,
+    #[case] chunks: Vec<OpenAIStreamChunk>,
+    #[case] message: Value,
+    #[case] finish_reason: &str,
+) {
+    let model = "openai/gpt-4.1";
+    let Ok(request) = serde_json::from_value::<ClaudeMessagesRequest>(json!({
+        "model": model,
+        "max_tokens": 1024,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "Read the README" }]
+    })) else {
+        return insta::assert_snapshot!(name, "invalid test request");
+    };
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+    let mock_response = helpers::mock_response_from_chunks(chunks);
+    let events = convert_openai_stream_to_events(
+        mock_response,
+        model,
+        &adapter,
+        &request,
+        &helpers::mock_app_state(),
+        StreamOptions::default(),
+    );
+    let aggregated = match aggregate_events(events).await {
+        Ok(aggregated) => aggregated,
+        Err(e) => return insta::assert_snapshot!(name, e.to_string()),
+    };
+
+    let response = json!({
+        "id": "chatcmpl-123",
+        "choices": [{ "index": 0, "message": message, "finish_reason": finish_reason }]
+    });
+    let non_stream = convert_openai_to_claude(
+        &response,
+        model,
+        &[],
+        &ToolMapping::default(),
+        &Config::default(),
+    );
+
+    let summary = json!({
+        "matches_non_stream": aggregated["content"] == non_stream["content"]
+            && aggregated["stop_reason"] == non_stream["stop_reason"],
+        "content": aggregated["content"],
+        "stop_reason": aggregated["stop_reason"],
+        "model": aggregated["model"],
+    });
+    insta::assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&summary).unwrap_or_default()
+    );
+}
+
+// usage estimated in the stream is reported through the header, not the marker in the body
+#[tokio::test]
+async fn verify_aggregated_usage_estimate() {
+    let model = "openai/gpt-4.1";
+    let Ok(request) = serde_json::from_value::<ClaudeMessagesRequest>(json!({
+        "model": model,
+        "max_tokens": 1024,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "Write a haiku about the sea." }]
+    })) else {
+        return insta::assert_snapshot!("invalid test request");
+    };
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+    let openai_request = convert_claude_to_openai(request.clone(), model, &adapter);
+    let chunks = vec![text_chunk("Waves fold into foam"), final_chunk("stop")];
+    let events = convert_openai_stream_to_events(
+        helpers::mock_response_from_chunks(chunks),
+        model,
+        &adapter,
+        &request,
+        &helpers::mock_app_state(),
+        StreamOptions {
+            usage_fallback: Some(openai_request.clone()),
+            ..StreamOptions::default()
+        },
+    );
+    let mut aggregated = match aggregate_events(events).await {
+        Ok(aggregated) => aggregated,
+        Err(e) => return insta::assert_snapshot!(e.to_string()),
+    };
+    let streamed_usage = aggregated["usage"].clone();
+    let estimated = estimate_response_usage(&mut aggregated, &openai_request);
+
+    let summary = json!({
+        "streamed_usage": streamed_usage,
+        "usage": aggregated["usage"],
+        "estimated": estimated,
+    });
+    insta::assert_snapshot!(serde_json::to_string_pretty(&summary).unwrap_or_default());
+}
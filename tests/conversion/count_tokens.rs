@@ -0,0 +1,117 @@
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::request::convert_claude_to_openai,
+    directives::models::{Settings, ThinkingHistory},
+    models::claude::{
+        ClaudeContent, ClaudeCountTokensRequest, ClaudeMessage, ClaudeMessagesRequest,
+        ClaudeSystem, ClaudeTool, ClaudeToolDefinition,
+    },
+    tokenizer::count_request_tokens,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rstest::rstest;
+use serde_json::json;
+
+fn count_tokens_request(model: &str) -> ClaudeCountTokensRequest {
+    ClaudeCountTokensRequest {
+        model: model.to_string(),
+        messages: vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ClaudeContent::Text("What is the weather like in San Francisco?".to_string()),
+        }],
+        system: Some(ClaudeSystem::Text(
+            "You are a helpful assistant.".to_string(),
+        )),
+        tools: Some(vec![ClaudeToolDefinition::Custom(ClaudeTool {
+            name: "get_weather".to_string(),
+            description: Some("Get the current weather in a given location.".to_string()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "location": {
+                        "type": "string",
+                        "description": "The city and state, e.g. San Francisco, CA"
+                    }
+                },
+                "required": ["location"]
+            }),
+            cache_control: None,
+        })]),
+        tool_choice: None,
+        thinking: None,
+    }
+}
+
+#[rstest]
+#[case("count_tokens_native", "openai/gpt-4.1")]
+#[case("count_tokens_xml_tools", "google/gemini-2.5-pro-xml-tools")]
+#[case("count_tokens_bracket_tools", "google/gemini-2.5-pro-bracket-tools")]
+fn verify_count_tokens(#[case] snapshot_name: &str, #[case] model: &str) {
+    let request = ClaudeMessagesRequest::from(count_tokens_request(model));
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+    let openai_request = convert_claude_to_openai(request, model, &adapter);
+    insta::assert_debug_snapshot!(snapshot_name, count_request_tokens(&openai_request));
+}
+
+// a file part is estimated per pdf page, the page tree object is not a page
+#[test]
+fn verify_count_tokens_pdf_file() {
+    let model = "openai/gpt-4.1";
+    let pdf = STANDARD.encode(
+        "%PDF-1.4\n1 0 obj << /Type /Pages /Count 2 >> endobj\n2 0 obj << /Type /Page >> endobj\n3 0 obj << /Type/Page >> endobj\n",
+    );
+    let request = serde_json::from_value::<ClaudeMessagesRequest>(json!({
+        "model": model,
+        "max_tokens": 1024,
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "document", "source": { "type": "base64", "media_type": "application/pdf", "data": pdf } },
+                { "type": "text", "text": "Summarize this." }
+            ]
+        }]
+    }))
+    .ok()
+    .map(|request| {
+        let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+        count_request_tokens(&convert_claude_to_openai(request, model, &adapter))
+    });
+    insta::assert_debug_snapshot!("count_tokens_pdf_file", request);
+}
+
+// replayed thinking history costs input tokens like any other content
+#[rstest]
+#[case(
+    "count_tokens_thinking_reasoning_content",
+    ThinkingHistory::ReasoningContent
+)]
+#[case("count_tokens_thinking_dropped", ThinkingHistory::Drop)]
+fn verify_count_tokens_thinking_history(#[case] name: &str, #[case] policy: ThinkingHistory) {
+    let model = "openai/gpt-4.1";
+    let settings = Settings {
+        thinking_history: Some(policy),
+        ..Settings::default()
+    };
+    let count = serde_json::from_value::<ClaudeMessagesRequest>(json!({
+        "model": model,
+        "max_tokens": 1024,
+        "messages": [
+            { "role": "user", "content": "What is 17 times 23?" },
+            {
+                "role": "assistant",
+                "content": [
+                    { "type": "thinking", "thinking": "17 times 20 is 340, 17 times 3 is 51, so the product is 391.", "signature": "sig" },
+                    { "type": "text", "text": "391" }
+                ]
+            },
+            { "role": "user", "content": "And times 2?" }
+        ]
+    }))
+    .ok()
+    .map(|request| {
+        let adapter = RequestAdapter::for_model(model, &settings, &Config::default());
+        count_request_tokens(&convert_claude_to_openai(request, model, &adapter))
+    });
+    insta::assert_debug_snapshot!(name, count);
+}
@@ -0,0 +1,167 @@
+use ant_compat::{
+    config::Config,
+    conversion::images::{ImageFetcher, inline_image_urls},
+    models::openai::{OpenAIContent, OpenAIContentPart, OpenAIImageUrl, OpenAIMessage},
+};
+use axum::{
+    Router,
+    body::Body,
+    http::{
+        StatusCode,
+        header::{CONTENT_TYPE, LOCATION},
+    },
+    routing::get,
+};
+use bytes::Bytes;
+use futures_util::{StreamExt, stream};
+use insta::assert_snapshot;
+use rstest::rstest;
+use tokio::net::TcpListener;
+
+// serves a png, an html page, a png that never ends, a png that stalls, a redirect to the png
+// under another name and nothing else
+async fn spawn_image_host() -> String {
+    let Ok(listener) = TcpListener::bind("127.0.0.1:0").await else {
+        return "http://127.0.0.1:9".to_string();
+    };
+    let address = listener
+        .local_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_default();
+    let redirect_target = format!(
+        "http://{}/cat.png",
+        address.replace("127.0.0.1", "localhost")
+    );
+    let app = Router::new()
+        .route(
+            "/cat.png",
+            get(|| async { ([(CONTENT_TYPE, "image/png")], b"\x89PNG\r\n\x1a\n".to_vec()) }),
+        )
+        .route(
+            "/endless.png",
+            get(|| async {
+                let chunk = Bytes::from(vec![0; 1024 * 1024]);
+                let body = stream::repeat_with(move || Ok::<_, std::io::Error>(chunk.clone()));
+                ([(CONTENT_TYPE, "image/png")], Body::from_stream(body))
+            }),
+        )
+        .route(
+            "/stalled.png",
+            get(|| async {
+                let body =
+                    stream::once(async { Ok::<_, std::io::Error>(Bytes::from_static(b"\x89PNG")) })
+                        .chain(stream::pending());
+                ([(CONTENT_TYPE, "image/png")], Body::from_stream(body))
+            }),
+        )
+        .route(
+            "/redirect",
+            get(move || async move { (StatusCode::FOUND, [(LOCATION, redirect_target)]) }),
+        )
+        .route(
+            "/page",
+            get(|| async {
+                (
+                    [(CONTENT_TYPE, "text/html; charset=utf-8")],
+                    "<html></html>",
+                )
+            }),
+        );
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    format!("http://{address}")
+}
+
+// the test host listens on loopback, which only an allowed host may resolve to
+fn fetcher(allowed_hosts: &[&str]) -> Option<ImageFetcher> {
+    ImageFetcher::new(&Config {
+        inline_image_hosts: allowed_hosts.iter().map(ToString::to_string).collect(),
+        ..Config::default()
+    })
+    .ok()
+}
+
+async fn inline(mut messages: Vec<OpenAIMessage>, allowed_hosts: &[&str]) -> String {
+    let Some(fetcher) = fetcher(allowed_hosts) else {
+        return "the image client did not build".to_string();
+    };
+    inline_image_urls(&mut messages, &fetcher).await;
+    serde_json::to_string_pretty(&messages).unwrap_or_default()
+}
+
+fn image_message(url: String) -> OpenAIMessage {
+    OpenAIMessage {
+        role: "user".to_string(),
+        content: Some(OpenAIContent::Array(vec![OpenAIContentPart {
+            part_type: "image_url".to_string(),
+            text: None,
+            image_url: Some(OpenAIImageUrl { url }),
+            file: None,
+            cache_control: None,
+        }])),
+        ..Default::default()
+    }
+}
+
+#[rstest]
+#[case("inline_image_fetched", "/cat.png")]
+#[case("inline_image_not_an_image", "/page")]
+#[case("inline_image_not_found", "/missing.png")]
+#[case("inline_image_too_large", "/endless.png")]
+#[tokio::test]
+async fn verify_inline_image_urls(#[case] name: &str, #[case] path: &str) {
+    let host = spawn_image_host().await;
+    let messages = vec![
+        image_message("data:image/png;base64,iVBORw0KGgo=".to_string()),
+        image_message(format!("{host}{path}")),
+    ];
+    let result = inline(messages, &["127.0.0.1"]).await;
+    assert_snapshot!(name, result.replace(&host, "[host]"));
+}
+
+// time is paused, the runtime skips ahead to the timeout once the stalled body is the only thing left
+#[tokio::test(start_paused = true)]
+async fn verify_inline_image_timeout() {
+    let host = spawn_image_host().await;
+    let messages = vec![image_message(format!("{host}/stalled.png"))];
+    let result = inline(messages, &["127.0.0.1"]).await;
+    assert_snapshot!("inline_image_timeout", result.replace(&host, "[host]"));
+}
+
+// clients must not be able to make the proxy fetch from its own network, {port} is the test host's
+#[rstest]
+#[case("inline_image_loopback", "http://127.0.0.1:{port}/cat.png", &[])]
+#[case("inline_image_resolves_to_loopback", "http://localhost:{port}/cat.png", &[])]
+#[case("inline_image_ipv6_loopback", "http://[::1]:{port}/cat.png", &[])]
+#[case("inline_image_unspecified", "http://0.0.0.0:{port}/cat.png", &[])]
+#[case("inline_image_private", "http://10.0.0.1/cat.png", &[])]
+#[case("inline_image_link_local", "http://169.254.169.254/latest/meta-data/", &[])]
+#[case("inline_image_not_http", "file:///etc/passwd", &[])]
+#[case("inline_image_host_not_allowed", "http://localhost:{port}/cat.png", &["127.0.0.1"])]
+#[case("inline_image_redirect_not_allowed", "http://127.0.0.1:{port}/redirect", &["127.0.0.1"])]
+#[tokio::test]
+async fn verify_inline_image_rejected(
+    #[case] name: &str,
+    #[case] url: &str,
+    #[case] allowed_hosts: &[&str],
+) {
+    let host = spawn_image_host().await;
+    let port = host.rsplit(':').next().unwrap_or_default();
+    let messages = vec![image_message(url.replace("{port}", port))];
+    let result = inline(messages, allowed_hosts).await;
+    assert_snapshot!(name, result.replace(port, "[port]"));
+}
+
+// one dead link only replaces its own image, the rest of the request still goes out
+#[tokio::test]
+async fn verify_inline_image_partial_failure() {
+    let host = spawn_image_host().await;
+    let messages = vec![
+        image_message(format!("{host}/missing.png")),
+        image_message(format!("{host}/cat.png")),
+    ];
+    let result = inline(messages, &["127.0.0.1"]).await;
+    assert_snapshot!(
+        "inline_image_partial_failure",
+        result.replace(&host, "[host]")
+    );
+}
@@ -1,2 +1,12 @@
-mod scenarios;
+mod aggregate;
+mod count_tokens;
+mod images;
+mod models;
+mod non_stream;
+mod request;
+mod server_tools;
+mod tool_arguments;
+pub mod scenarios;
 mod stream;
+mod tool_mapping;
+mod usage;
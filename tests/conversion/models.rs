@@ -0,0 +1,43 @@
+use ant_compat::{
+    conversion::convert_openai_models_to_claude,
+    models::{
+        claude::ClaudeModelListQuery,
+        openai::{OpenAIModel, OpenAIModelList},
+    },
+};
+use rstest::rstest;
+
+fn upstream_models() -> OpenAIModelList {
+    OpenAIModelList {
+        data: ["openai/gpt-4.1", "google/gemini-2.5-pro", "zai-org/glm-4.5"]
+            .into_iter()
+            .map(|id| OpenAIModel {
+                id: id.to_string(),
+                created: Some(1_754_006_400),
+                owned_by: None,
+            })
+            .collect(),
+    }
+}
+
+fn query(
+    before_id: Option<&str>,
+    after_id: Option<&str>,
+    limit: Option<usize>,
+) -> ClaudeModelListQuery {
+    ClaudeModelListQuery {
+        before_id: before_id.map(str::to_string),
+        after_id: after_id.map(str::to_string),
+        limit,
+    }
+}
+
+#[rstest]
+#[case("models_default_page", query(None, None, None))]
+#[case("models_limit", query(None, None, Some(2)))]
+#[case("models_after_id", query(None, Some("google/gemini-2.5-pro"), Some(3)))]
+#[case("models_before_id", query(Some("zai-org/glm-4.5"), None, Some(2)))]
+fn verify_model_list_conversion(#[case] snapshot_name: &str, #[case] query: ClaudeModelListQuery) {
+    let result = convert_openai_models_to_claude(&upstream_models(), &query);
+    insta::assert_debug_snapshot!(snapshot_name, result);
+}
@@ -0,0 +1,48 @@
+use ant_compat::{
+    config::Config,
+    conversion::{non_stream::convert_openai_to_claude, tool_mapping::ToolMapping},
+};
+use insta::assert_snapshot;
+use rstest::rstest;
+use serde_json::{Value, json};
+
+fn completion(content: &str, finish_reason: &str, choice_extra: &Value) -> Value {
+    let mut choice = json!({
+        "index": 0,
+        "message": { "role": "assistant", "content": content },
+        "finish_reason": finish_reason,
+    });
+    if let (Some(choice), Some(extra)) = (choice.as_object_mut(), choice_extra.as_object()) {
+        choice.extend(extra.clone());
+    }
+    json!({
+        "id": "chatcmpl-123",
+        "choices": [choice],
+        "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+    })
+}
+
+#[rstest]
+#[case("non_stream_stop_reported", completion("1, 2, 3", "stop", &json!({ "stop_reason": "END" })))]
+#[case("non_stream_matched_stop", completion("1, 2, 3", "stop", &json!({ "matched_stop": "STOP" })))]
+#[case("non_stream_stop_echoed", completion("1, 2, 3 STOP", "stop", &json!({})))]
+#[case("non_stream_stop_not_hit", completion("1, 2, 3", "stop", &json!({ "stop_reason": "<|im_end|>" })))]
+#[case("non_stream_content_filter", completion("1, 2", "content_filter", &json!({})))]
+fn verify_non_stream_stop_sequence(#[case] name: &str, #[case] response: Value) {
+    let stop_sequences = vec!["END".to_string(), "STOP".to_string()];
+    let converted = convert_openai_to_claude(
+        &response,
+        "test-model",
+        &stop_sequences,
+        &ToolMapping::default(),
+        &Config::default(),
+    );
+    let fields = json!({
+        "stop_reason": converted["stop_reason"],
+        "stop_sequence": converted["stop_sequence"],
+    });
+    assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&fields).unwrap_or_default()
+    );
+}
@@ -13,6 +13,7 @@ pub fn text_chunk(content: &str) -> OpenAIStreamChunk {
                 ..Default::default()
             },
             finish_reason: None,
+            ..Default::default()
         }],
         model: "kimi-k2-0711-preview".to_string(),
         usage: OpenAIUsage::default(),
@@ -32,16 +33,17 @@ pub fn tool_chunk_partial(
             delta: OpenAIDelta {
                 tool_calls: Some(vec![OpenAIStreamToolCall {
                     index,
-                    id: id.map(|s| s.to_string()),
+                    id: id.map(ToString::to_string),
                     call_type: Some("function".to_string()),
                     function: Some(OpenAIStreamFunction {
-                        name: name.map(|s| s.to_string()),
-                        arguments: args.map(|s| s.to_string()),
+                        name: name.map(ToString::to_string),
+                        arguments: args.map(ToString::to_string),
                     }),
                 }]),
                 ..Default::default()
             },
             finish_reason: None,
+            ..Default::default()
         }],
         model: "bedrock/claude-sonnet-4-20250514".to_string(),
         usage: OpenAIUsage::default(),
@@ -66,6 +68,7 @@ pub fn tool_chunk(index: u32, id: &str, name: &str, args: &str) -> OpenAIStreamC
                 ..Default::default()
             },
             finish_reason: None,
+            ..Default::default()
         }],
         model: "kimi-k2-0711-preview".to_string(),
         usage: OpenAIUsage::default(),
@@ -82,6 +85,7 @@ pub fn thinking_chunk(content: &str) -> OpenAIStreamChunk {
                 ..Default::default()
             },
             finish_reason: None,
+            ..Default::default()
         }],
         model: "o3".to_string(),
         usage: OpenAIUsage::default(),
@@ -95,6 +99,7 @@ pub fn final_chunk(reason: &str) -> OpenAIStreamChunk {
             index: 0,
             delta: OpenAIDelta::default(),
             finish_reason: Some(reason.to_string()),
+            ..Default::default()
         }],
         model: "gpt-4.1-mini".to_string(),
         usage: OpenAIUsage::default(),
@@ -0,0 +1,62 @@
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::request::convert_claude_to_openai,
+    directives::models::{ResponsesSettings, Settings},
+    models::claude::ClaudeMessagesRequest,
+};
+use insta::assert_snapshot;
+use rstest::rstest;
+use serde_json::{Value, json};
+
+fn server_tools_request() -> Value {
+    json!({
+        "model": "openai/gpt-5",
+        "max_tokens": 1024,
+        "tools": [
+            { "name": "Read", "description": "Read a file", "input_schema": { "type": "object", "properties": {} } },
+            {
+                "type": "web_search_20250305",
+                "name": "web_search",
+                "max_uses": 5,
+                "allowed_domains": ["docs.rs"],
+                "user_location": { "type": "approximate", "city": "Tokyo", "country": "JP" }
+            },
+            { "type": "web_fetch_20250910", "name": "web_fetch" },
+            { "type": "bash_20250124", "name": "bash" },
+            { "type": "text_editor_20250728", "name": "str_replace_based_edit_tool" }
+        ],
+        "tool_choice": { "type": "tool", "name": "web_search" },
+        "messages": [{ "role": "user", "content": "What changed in the latest tokio release?" }]
+    })
+}
+
+#[rstest]
+#[case("server_tools_chat_completions", false)]
+#[case("server_tools_responses", true)]
+fn verify_server_tools(#[case] name: &str, #[case] responses: bool) {
+    let request: ClaudeMessagesRequest = match serde_json::from_value(server_tools_request()) {
+        Ok(request) => request,
+        Err(e) => return assert_snapshot!(name, format!("invalid test request: {e}")),
+    };
+    let settings = Settings {
+        responses: responses.then(|| ResponsesSettings {
+            enable: Some(true),
+            ..ResponsesSettings::default()
+        }),
+        ..Settings::default()
+    };
+    let model = request.model.clone();
+    let adapter = RequestAdapter::for_model(&model, &settings, &Config::default());
+    let openai_request = convert_claude_to_openai(request.clone(), &model, &adapter);
+    let body = adapter.build_request_body(&openai_request, &request);
+    let converted = json!({
+        "tools": body["tools"],
+        "tool_choice": body["tool_choice"],
+        "dropped": adapter.dropped_server_tools(&request),
+    });
+    assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&converted).unwrap_or_default()
+    );
+}
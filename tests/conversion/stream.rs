@@ -2,14 +2,23 @@ use super::scenarios::{final_chunk, text_chunk, thinking_chunk, tool_chunk, tool
 use crate::helpers;
 use ant_compat::{
     adapters::RequestAdapter,
-    conversion::stream::convert_openai_stream_to_anthropic,
-    directives::models::Settings,
+    config::Config,
+    conversion::{
+        stream::{
+            StreamOptions, chunks_to_events, convert_openai_stream_to_anthropic, events_to_sse,
+        },
+        tool_mapping::ToolMapping,
+    },
+    directives::models::{ResponsesSettings, Settings},
     models::{
         claude::{AnthropicStreamEvent, ClaudeMessagesRequest, MessageStart},
         openai::{OpenAIDelta, OpenAIStreamChoice, OpenAIStreamChunk, OpenAIUsage},
     },
 };
+use futures_util::StreamExt;
 use rstest::rstest;
+use serde_json::{Value, json};
+use std::fmt::Write;
 
 fn redact_message_ids(events: &mut [AnthropicStreamEvent]) {
     for event in events {
@@ -41,6 +50,7 @@ fn redact_message_ids(events: &mut [AnthropicStreamEvent]) {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             },
             OpenAIStreamChoice {
                 index: 1,
@@ -49,6 +59,7 @@ fn redact_message_ids(events: &mut [AnthropicStreamEvent]) {
                     ..Default::default()
                 },
                 finish_reason: None,
+                ..Default::default()
             },
         ],
         model: "gpt-4.1".to_string(),
@@ -79,7 +90,17 @@ fn redact_message_ids(events: &mut [AnthropicStreamEvent]) {
 )]
 #[tokio::test]
 async fn verify_stream_conversion(#[case] name: &str, #[case] chunks: Vec<OpenAIStreamChunk>) {
-    let mock_response = crate::helpers::mock_response_from_chunks(chunks).await;
+    let mut output_events = convert_chunks(chunks, None).await;
+    redact_message_ids(&mut output_events);
+
+    insta::assert_debug_snapshot!(name, output_events);
+}
+
+async fn convert_chunks(
+    chunks: Vec<OpenAIStreamChunk>,
+    stop_sequences: Option<Vec<String>>,
+) -> Vec<AnthropicStreamEvent> {
+    let mock_response = crate::helpers::mock_response_from_chunks(chunks);
     let model = "test-model";
     let request = ClaudeMessagesRequest {
         model: model.to_string(),
@@ -87,22 +108,322 @@ async fn verify_stream_conversion(#[case] name: &str, #[case] chunks: Vec<OpenAI
         max_tokens: 1024,
         stream: Some(true),
         system: None,
-        stop_sequences: None,
+        stop_sequences,
         temperature: None,
         top_p: None,
         top_k: None,
         tools: None,
         tool_choice: None,
         thinking: None,
+        metadata: None,
+        service_tier: None,
     };
-    let adapter = RequestAdapter::for_model(model, &Settings::default());
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
     let mock_state = helpers::mock_app_state();
 
-    let anthropic_stream =
-        convert_openai_stream_to_anthropic(mock_response, model, &adapter, &request, &mock_state);
+    let anthropic_stream = convert_openai_stream_to_anthropic(
+        mock_response,
+        model,
+        &adapter,
+        &request,
+        &mock_state,
+        StreamOptions::default(),
+    );
 
-    let mut output_events = crate::helpers::collect_and_parse_stream(anthropic_stream).await;
-    redact_message_ids(&mut output_events);
+    crate::helpers::collect_and_parse_stream(anthropic_stream).await
+}
 
-    insta::assert_debug_snapshot!(name, output_events);
+fn with_stop_reason(mut chunk: OpenAIStreamChunk, stop_reason: &Value) -> OpenAIStreamChunk {
+    for choice in &mut chunk.choices {
+        choice.stop_reason = Some(stop_reason.clone());
+    }
+    chunk
+}
+
+#[rstest]
+#[case("stop_sequence_reported", vec![text_chunk("1, 2, 3"), with_stop_reason(final_chunk("stop"), &json!("END"))])]
+#[case("stop_sequence_token_id", vec![text_chunk("1, 2, 3"), with_stop_reason(final_chunk("stop"), &json!(151_645))])]
+#[case("stop_sequence_echoed", vec![text_chunk("1, 2, 3 ST"), text_chunk("OP"), final_chunk("stop")])]
+#[case("stop_sequence_not_hit", vec![text_chunk("1, 2, 3"), final_chunk("stop")])]
+#[case("stop_sequence_content_filter", vec![text_chunk("1, 2, 3"), final_chunk("content_filter")])]
+#[tokio::test]
+async fn verify_stop_sequence(#[case] name: &str, #[case] chunks: Vec<OpenAIStreamChunk>) {
+    let stop_sequences = vec!["END".to_string(), "STOP".to_string()];
+    let message_deltas: Vec<_> = convert_chunks(chunks, Some(stop_sequences))
+        .await
+        .into_iter()
+        .filter(|event| matches!(event, AnthropicStreamEvent::MessageDelta(_)))
+        .collect();
+
+    insta::assert_debug_snapshot!(name, message_deltas);
+}
+
+// the same upstream usage has to come out identical from the streaming and non-streaming paths
+#[rstest]
+#[case("cache_usage_none", json!({ "prompt_tokens": 100, "completion_tokens": 5 }))]
+#[case(
+    "cache_usage_openai",
+    json!({ "prompt_tokens": 100, "completion_tokens": 5, "prompt_tokens_details": { "cached_tokens": 80 } })
+)]
+#[case(
+    "cache_usage_openrouter",
+    json!({
+        "prompt_tokens": 100,
+        "completion_tokens": 5,
+        "prompt_tokens_details": { "cached_tokens": 0, "cache_write_tokens": 90 }
+    })
+)]
+#[case(
+    "cache_usage_litellm",
+    json!({
+        "prompt_tokens": 100,
+        "completion_tokens": 5,
+        "cache_read_input_tokens": 60,
+        "cache_creation_input_tokens": 30
+    })
+)]
+#[tokio::test]
+async fn verify_cache_usage(#[case] name: &str, #[case] usage: Value) {
+    let mut last = final_chunk("stop");
+    last.usage = serde_json::from_value(usage.clone()).unwrap_or_default();
+    let stream_usage = convert_chunks(vec![text_chunk("Hi"), last], None)
+        .await
+        .into_iter()
+        .find_map(|event| match event {
+            AnthropicStreamEvent::MessageDelta(delta) => serde_json::to_value(delta.usage).ok(),
+            _ => None,
+        });
+    let response = json!({
+        "id": "chatcmpl-123",
+        "choices": [{ "index": 0, "message": { "role": "assistant", "content": "Hi" }, "finish_reason": "stop" }],
+        "usage": usage
+    });
+    let non_stream = ant_compat::conversion::non_stream::convert_openai_to_claude(
+        &response,
+        "test-model",
+        &[],
+        &ToolMapping::default(),
+        &Config::default(),
+    );
+    let usage = json!({ "stream": stream_usage, "non_stream": non_stream["usage"] });
+    insta::assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&usage).unwrap_or_default()
+    );
+}
+
+// cache details in an earlier chunk survive a final chunk that only reports the output
+#[tokio::test]
+async fn verify_cache_usage_split() {
+    let mut first = text_chunk("Hi");
+    first.usage = serde_json::from_value(
+        json!({ "prompt_tokens": 100, "prompt_tokens_details": { "cached_tokens": 80 } }),
+    )
+    .unwrap_or_default();
+    let mut last = final_chunk("stop");
+    last.usage.completion_tokens = 5;
+    let stream_usage = convert_chunks(vec![first, last], None)
+        .await
+        .into_iter()
+        .find_map(|event| match event {
+            AnthropicStreamEvent::MessageDelta(delta) => serde_json::to_value(delta.usage).ok(),
+            _ => None,
+        });
+    insta::assert_snapshot!(
+        "cache_usage_split",
+        serde_json::to_string_pretty(&stream_usage).unwrap_or_default()
+    );
+}
+
+// the first chunk arrives after 50ms, only a deadline above that lets message_start wait for it
+#[rstest]
+#[case("message_start_immediate", false, 1000)]
+#[case("message_start_deferred", true, 1000)]
+#[case("message_start_deadline_passed", true, 10)]
+#[tokio::test]
+async fn verify_deferred_message_start(
+    #[case] name: &str,
+    #[case] defer: bool,
+    #[case] deadline_ms: u64,
+) {
+    let mut first = text_chunk("Hi");
+    first.id = "chatcmpl-B9MBs8CjcvOU2jLn4n570S5qMJKcT".to_string();
+    first.usage.prompt_tokens = 42;
+    let chunks = futures_util::stream::once(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        Ok(first)
+    })
+    .chain(futures_util::stream::iter(vec![Ok(final_chunk("stop"))]));
+    let config = Config {
+        message_start_deadline_ms: deadline_ms,
+        ..Config::default()
+    };
+    let events = chunks_to_events(
+        "gpt-4.1",
+        Box::pin(chunks),
+        Vec::new(),
+        ToolMapping::default(),
+        defer,
+        &config,
+    );
+    let mut message_start: Vec<_> = events
+        .take(1)
+        .filter_map(|event| async move { event.ok() })
+        .collect()
+        .await;
+    redact_timestamp_ids(&mut message_start);
+    insta::assert_debug_snapshot!(name, message_start);
+}
+
+// a ping written while message_start waits for the first chunk would be the first sse frame
+#[tokio::test(start_paused = true)]
+async fn verify_deferred_message_start_comes_first() {
+    let chunks = futures_util::stream::once(async {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        Ok(text_chunk("Hi"))
+    })
+    .chain(futures_util::stream::iter(vec![Ok(final_chunk("stop"))]));
+    let events = chunks_to_events(
+        "gpt-4.1",
+        Box::pin(chunks),
+        Vec::new(),
+        ToolMapping::default(),
+        true,
+        &Config::default(),
+    );
+    let frames: Vec<String> = events_to_sse(events, std::time::Duration::from_secs(1))
+        .take(2)
+        .filter_map(|frame| async move { frame.ok() })
+        .map(|frame| {
+            String::from_utf8_lossy(&frame)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+        .await;
+    assert_eq!(
+        frames,
+        ["event: message_start", "event: content_block_start"]
+    );
+}
+
+// ids only made up of the millisecond timestamp change on every run
+fn redact_timestamp_ids(events: &mut [AnthropicStreamEvent]) {
+    for event in events {
+        if let AnthropicStreamEvent::MessageStart(MessageStart { message, .. }) = event
+            && message
+                .id
+                .strip_prefix("msg_")
+                .is_some_and(|suffix| suffix.chars().all(|c| c.is_ascii_digit()))
+        {
+            message.id = "msg_[timestamp]".to_string();
+        }
+    }
+}
+
+// a cut off answer has to reach the client as an error, not as a normal message_stop
+#[rstest]
+#[case("stream_truncated", false)]
+#[case("stream_idle_timeout", true)]
+#[tokio::test]
+async fn verify_unfinished_stream(#[case] name: &str, #[case] stall: bool) {
+    let chunks = futures_util::stream::iter(vec![Ok(text_chunk("Hel"))]);
+    let chunks = if stall {
+        chunks.chain(futures_util::stream::pending()).boxed()
+    } else {
+        chunks.boxed()
+    };
+    let config = Config {
+        idle_connection_timeout: 1,
+        ..Config::default()
+    };
+    let events: Vec<_> = chunks_to_events(
+        "gpt-4.1",
+        chunks,
+        Vec::new(),
+        ToolMapping::default(),
+        false,
+        &config,
+    )
+    .map(|event| match event {
+        Ok(event) => json!(event.to_parts().0),
+        Err(error) => error.to_error_body(),
+    })
+    .collect()
+    .await;
+    insta::assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&events).unwrap_or_default()
+    );
+}
+
+// the responses api ends a cut off answer with response.incomplete, which is a stop reason and not truncation
+#[rstest]
+#[case("responses_incomplete_max_tokens", "max_output_tokens")]
+#[case("responses_incomplete_content_filter", "content_filter")]
+#[tokio::test]
+async fn verify_responses_incomplete(#[case] name: &str, #[case] reason: &str) {
+    let events = [
+        json!({ "type": "response.output_text.delta", "delta": "Hel" }),
+        json!({
+            "type": "response.incomplete",
+            "response": {
+                "status": "incomplete",
+                "incomplete_details": { "reason": reason },
+                "usage": { "input_tokens": 12, "output_tokens": 1 }
+            }
+        }),
+    ];
+    let body = events.iter().fold(String::new(), |mut body, event| {
+        let _ = write!(body, "data: {event}\n\n");
+        body
+    });
+    let response = reqwest::Response::from(http::Response::new(reqwest::Body::from(body)));
+    let request = ClaudeMessagesRequest {
+        model: "openai/gpt-4.1".to_string(),
+        messages: vec![],
+        max_tokens: 1,
+        stream: Some(true),
+        system: None,
+        stop_sequences: None,
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+        service_tier: None,
+    };
+    let settings = Settings {
+        responses: Some(ResponsesSettings {
+            enable: Some(true),
+            ..ResponsesSettings::default()
+        }),
+        ..Settings::default()
+    };
+    let adapter = RequestAdapter::for_model("openai/gpt-4.1", &settings, &Config::default());
+    let events: Vec<_> = chunks_to_events(
+        "gpt-4.1",
+        adapter.chunk_stream(response, &request),
+        Vec::new(),
+        ToolMapping::default(),
+        false,
+        &Config::default(),
+    )
+    .filter_map(|event| async move {
+        match event {
+            Ok(event @ AnthropicStreamEvent::MessageDelta(_)) => Some(event.to_parts().1),
+            Ok(_) => None,
+            Err(error) => Some(error.to_error_body()),
+        }
+    })
+    .collect()
+    .await;
+    insta::assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&events).unwrap_or_default()
+    );
 }
@@ -0,0 +1,115 @@
+use super::scenarios::{final_chunk, tool_chunk};
+use crate::helpers;
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::{
+        non_stream::convert_openai_to_claude,
+        stream::{StreamOptions, convert_openai_stream_to_anthropic},
+        tool_mapping::ToolMapping,
+    },
+    directives::models::Settings,
+    models::claude::{AnthropicStreamEvent, ClaudeMessagesRequest, Delta},
+};
+use rstest::rstest;
+use serde_json::json;
+
+// both paths have to hand the client a json object it can parse, whatever the model produced
+#[rstest]
+#[case("tool_arguments_valid", r#"{"path":"src/main.rs"}"#, "tool_calls")]
+#[case(
+    "tool_arguments_trailing_comma",
+    r#"{"path":"src/main.rs",}"#,
+    "tool_calls"
+)]
+#[case(
+    "tool_arguments_raw_newline",
+    "{\"content\":\"line one\nline two\"}",
+    "tool_calls"
+)]
+#[case(
+    "tool_arguments_truncated",
+    r#"{"path":"src/main.rs","content":"fn ma"#,
+    "length"
+)]
+#[case("tool_arguments_not_object", r#"["src/main.rs"]"#, "tool_calls")]
+#[tokio::test]
+async fn verify_tool_argument_repair(
+    #[case] name: &str,
+    #[case] arguments: &str,
+    #[case] finish_reason: &str,
+) {
+    let model = "openai/gpt-4.1";
+    let Ok(request) = serde_json::from_value::<ClaudeMessagesRequest>(json!({
+        "model": model,
+        "max_tokens": 1024,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "Write the file" }]
+    })) else {
+        return insta::assert_snapshot!(name, "invalid test request");
+    };
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+
+    let middle = arguments.len() / 2;
+    let split = (0..=middle)
+        .rev()
+        .find(|&index| arguments.is_char_boundary(index))
+        .unwrap_or(0);
+    let chunks = vec![
+        tool_chunk(0, "call_1", "Write", &arguments[..split]),
+        tool_chunk(0, "call_1", "Write", &arguments[split..]),
+        final_chunk(finish_reason),
+    ];
+    let mock_response = helpers::mock_response_from_chunks(chunks);
+    let stream = convert_openai_stream_to_anthropic(
+        mock_response,
+        model,
+        &adapter,
+        &request,
+        &helpers::mock_app_state(),
+        StreamOptions::default(),
+    );
+    let stream_input: String = helpers::collect_and_parse_stream(stream)
+        .await
+        .into_iter()
+        .filter_map(|event| match event {
+            AnthropicStreamEvent::ContentBlockDelta(delta) => match delta.delta {
+                Delta::InputJson { partial_json } => Some(partial_json),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    let response = json!({
+        "id": "chatcmpl-123",
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "Write", "arguments": arguments }
+                }]
+            },
+            "finish_reason": finish_reason
+        }]
+    });
+    let non_stream = convert_openai_to_claude(
+        &response,
+        model,
+        &[],
+        &ToolMapping::default(),
+        &Config::default(),
+    );
+
+    let summary = json!({
+        "stream": stream_input,
+        "non_stream": non_stream["content"][0]["input"],
+    });
+    insta::assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&summary).unwrap_or_default()
+    );
+}
@@ -0,0 +1,176 @@
+use super::scenarios::{final_chunk, tool_chunk};
+use crate::helpers;
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::{
+        non_stream::convert_openai_to_claude,
+        request::convert_claude_to_openai,
+        stream::{StreamOptions, convert_openai_stream_to_anthropic},
+        tool_mapping::ToolMapping,
+    },
+    directives::models::{Settings, ToolNaming},
+    models::claude::{AnthropicStreamEvent, ClaudeMessagesRequest, ContentBlock},
+};
+use insta::assert_snapshot;
+use rstest::rstest;
+use serde_json::{Value, json};
+
+const LONG_TOOL_NAME: &str =
+    "mcp__claude-in-chrome__navigate_to_the_given_url_and_wait_until_the_page_has_loaded";
+
+fn tool_history_request(model: &str, stream: bool) -> Value {
+    json!({
+        "model": model,
+        "max_tokens": 1024,
+        "stream": stream,
+        "tools": [
+            { "name": LONG_TOOL_NAME, "input_schema": { "type": "object", "properties": {} } },
+            { "name": "Read", "input_schema": { "type": "object", "properties": {} } }
+        ],
+        "tool_choice": { "type": "tool", "name": LONG_TOOL_NAME },
+        "messages": [
+            { "role": "user", "content": "Open the docs and read the notes" },
+            {
+                "role": "assistant",
+                "content": [
+                    { "type": "tool_use", "id": "toolu_01A09q90qw90lq917835lq9", "name": LONG_TOOL_NAME, "input": {} },
+                    { "type": "tool_use", "id": "toolu_enc_Y2FsbF9hYmMxMjM", "name": "Read", "input": {} },
+                    // valid base64url but not marked as encoded, so it must not be decoded
+                    { "type": "tool_use", "id": "toolu_Y2FsbF94eXo", "name": "Read", "input": {} }
+                ]
+            },
+            {
+                "role": "user",
+                "content": [
+                    { "type": "tool_result", "tool_use_id": "toolu_01A09q90qw90lq917835lq9", "content": "Loaded" },
+                    { "type": "tool_result", "tool_use_id": "toolu_enc_Y2FsbF9hYmMxMjM", "content": "Notes" },
+                    { "type": "tool_result", "tool_use_id": "toolu_Y2FsbF94eXo", "content": "Notes" }
+                ]
+            }
+        ]
+    })
+}
+
+fn parse_request(request: Value) -> Result<ClaudeMessagesRequest, String> {
+    serde_json::from_value(request).map_err(|e| format!("invalid test request: {e}"))
+}
+
+#[rstest]
+#[case("tool_naming_upstream", "openai/gpt-4.1", None)]
+#[case("tool_naming_mistral_default", "mistral/devstral-medium", None)]
+#[case("tool_naming_call", "openai/gpt-4.1", Some(ToolNaming::Call))]
+#[case("tool_naming_verbatim", "openai/gpt-4.1", Some(ToolNaming::Verbatim))]
+fn verify_tool_naming_request(
+    #[case] name: &str,
+    #[case] model: &str,
+    #[case] tool_naming: Option<ToolNaming>,
+) {
+    let request = match parse_request(tool_history_request(model, false)) {
+        Ok(request) => request,
+        Err(e) => return assert_snapshot!(name, e),
+    };
+    let settings = Settings {
+        tool_naming,
+        ..Settings::default()
+    };
+    let adapter = RequestAdapter::for_model(model, &settings, &Config::default());
+    let openai_request = convert_claude_to_openai(request, model, &adapter);
+    let tool_calls: Vec<_> = openai_request
+        .messages
+        .iter()
+        .flat_map(|message| message.tool_calls.iter().flatten())
+        .map(|call| json!({ "id": call.id, "name": call.function.name }))
+        .collect();
+    let tool_results: Vec<_> = openai_request
+        .messages
+        .iter()
+        .filter_map(|message| message.tool_call_id.clone())
+        .collect();
+    let converted = json!({
+        "tools": openai_request
+            .tools
+            .iter()
+            .flatten()
+            .map(|tool| tool.function.name.clone())
+            .collect::<Vec<_>>(),
+        "tool_choice": openai_request.tool_choice,
+        "tool_calls": tool_calls,
+        "tool_results": tool_results,
+    });
+    assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&converted).unwrap_or_default()
+    );
+}
+
+// the upstream only ever sees the shortened name, the client has to get the original back
+#[tokio::test]
+async fn verify_tool_naming_round_trip() {
+    let model = "openai/gpt-4.1";
+    let request = match parse_request(tool_history_request(model, true)) {
+        Ok(request) => request,
+        Err(e) => return assert_snapshot!("tool_naming_round_trip", e),
+    };
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+    let upstream_name = convert_claude_to_openai(request.clone(), model, &adapter)
+        .tools
+        .iter()
+        .flatten()
+        .map(|tool| tool.function.name.clone())
+        .next()
+        .unwrap_or_default();
+
+    let response = json!({
+        "id": "chatcmpl-123",
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "tool_calls": [{
+                    "id": "call_abc123",
+                    "type": "function",
+                    "function": { "name": upstream_name, "arguments": "{}" }
+                }]
+            },
+            "finish_reason": "tool_calls"
+        }]
+    });
+    let tool_mapping = ToolMapping::new(adapter.tool_naming(&request), &request);
+    let non_stream =
+        convert_openai_to_claude(&response, model, &[], &tool_mapping, &Config::default());
+
+    let chunks = vec![
+        tool_chunk(0, "call_abc123", &upstream_name, "{}"),
+        final_chunk("tool_calls"),
+    ];
+    let mock_response = helpers::mock_response_from_chunks(chunks);
+    let stream = convert_openai_stream_to_anthropic(
+        mock_response,
+        model,
+        &adapter,
+        &request,
+        &helpers::mock_app_state(),
+        StreamOptions::default(),
+    );
+    let stream_tool_use = helpers::collect_and_parse_stream(stream)
+        .await
+        .into_iter()
+        .find_map(|event| match event {
+            AnthropicStreamEvent::ContentBlockStart(start) => match start.content_block {
+                ContentBlock::ToolUse { id, name, .. } => Some(json!({ "id": id, "name": name })),
+                _ => None,
+            },
+            _ => None,
+        });
+
+    let summary = json!({
+        "upstream_name": upstream_name,
+        "non_stream": non_stream["content"],
+        "stream": stream_tool_use,
+    });
+    assert_snapshot!(
+        "tool_naming_round_trip",
+        serde_json::to_string_pretty(&summary).unwrap_or_default()
+    );
+}
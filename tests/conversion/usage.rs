@@ -0,0 +1,114 @@
+use super::scenarios::{final_chunk, text_chunk, tool_chunk};
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::{
+        non_stream::convert_openai_to_claude,
+        request::convert_claude_to_openai,
+        stream::chunks_to_events,
+        tool_mapping::ToolMapping,
+        usage::{estimate_missing_usage, estimate_response_usage},
+    },
+    directives::models::Settings,
+    models::{
+        claude::{AnthropicStreamEvent, ClaudeMessagesRequest},
+        openai::{OpenAIRequest, OpenAIStreamChunk},
+    },
+};
+use futures_util::StreamExt;
+use rstest::rstest;
+use serde_json::{Value, json};
+
+fn openai_request() -> Option<OpenAIRequest> {
+    let request: ClaudeMessagesRequest = serde_json::from_value(json!({
+        "model": "openai/gpt-4.1",
+        "max_tokens": 1024,
+        "system": "You are a helpful assistant.",
+        "messages": [{ "role": "user", "content": "Write a haiku about the sea." }]
+    }))
+    .ok()?;
+    let adapter =
+        RequestAdapter::for_model(&request.model, &Settings::default(), &Config::default());
+    Some(convert_claude_to_openai(
+        request,
+        "openai/gpt-4.1",
+        &adapter,
+    ))
+}
+
+fn with_usage(mut chunk: OpenAIStreamChunk, usage: &Value) -> OpenAIStreamChunk {
+    chunk.usage = serde_json::from_value(usage.clone()).unwrap_or_default();
+    chunk
+}
+
+// only the counters the upstream left at zero are estimated, reported ones pass through
+#[rstest]
+#[case("usage_estimated", json!({}))]
+#[case("usage_output_estimated", json!({ "prompt_tokens": 100 }))]
+#[case("usage_reported", json!({ "prompt_tokens": 100, "completion_tokens": 5 }))]
+#[tokio::test]
+async fn verify_usage_estimate(#[case] name: &str, #[case] usage: Value) {
+    let Some(openai_request) = openai_request() else {
+        return insta::assert_snapshot!(name, "invalid test request");
+    };
+    let chunks = vec![
+        text_chunk("Waves fold into foam"),
+        tool_chunk(1, "call_1", "get_tide", r#"{"port":"Brest"}"#),
+        with_usage(final_chunk("tool_calls"), &usage),
+    ];
+    let events = chunks_to_events(
+        "gpt-4.1",
+        futures_util::stream::iter(chunks.into_iter().map(Ok)).boxed(),
+        Vec::new(),
+        ToolMapping::default(),
+        false,
+        &Config::default(),
+    );
+    let stream_usage = estimate_missing_usage(events, openai_request.clone())
+        .filter_map(|event| async move {
+            match event {
+                Ok(AnthropicStreamEvent::MessageDelta(delta)) => {
+                    serde_json::to_value(delta.usage).ok()
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+    let response = json!({
+        "id": "chatcmpl-123",
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": "Waves fold into foam",
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "get_tide", "arguments": r#"{"port":"Brest"}"# }
+                }]
+            },
+            "finish_reason": "tool_calls"
+        }],
+        "usage": usage
+    });
+    let mut non_stream = convert_openai_to_claude(
+        &response,
+        "gpt-4.1",
+        &[],
+        &ToolMapping::default(),
+        &Config::default(),
+    );
+    let estimated = estimate_response_usage(&mut non_stream, &openai_request);
+
+    let summary = json!({
+        "stream": stream_usage,
+        "non_stream": non_stream["usage"],
+        "non_stream_estimated": estimated,
+    });
+    insta::assert_snapshot!(
+        name,
+        serde_json::to_string_pretty(&summary).unwrap_or_default()
+    );
+}
@@ -1,5 +1,6 @@
 use crate::helpers::load_system_prompt_fixture;
 use ant_compat::{
+    config::Config,
     directives::processor::DirectiveProcessor,
     models::claude::{ClaudeMessagesRequest, ClaudeSystem},
 };
@@ -20,6 +21,8 @@ fn base_request() -> ClaudeMessagesRequest {
         tools: None,
         tool_choice: None,
         thinking: None,
+        metadata: None,
+        service_tier: None,
     }
 }
 
@@ -96,7 +99,7 @@ fn test_proxy_directive_processing(#[case] name: &str, #[case] directive_block:
     };
     request.system = Some(ClaudeSystem::Text(system_prompt));
 
-    DirectiveProcessor::process(&mut request);
+    DirectiveProcessor::process(&mut request, &Config::default());
 
     assert_debug_snapshot!(name, request);
 }
@@ -0,0 +1,84 @@
+use ant_compat::{extract::AnthropicJson, models::claude::ClaudeMessagesRequest};
+use axum::{Router, body::Body, extract::DefaultBodyLimit, routing::post};
+use http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use rstest::rstest;
+use serde_json::Value;
+use tower::ServiceExt;
+
+const BODY_LIMIT: usize = 1024;
+
+fn router() -> Router {
+    Router::new()
+        .route(
+            "/v1/messages",
+            post(
+                |AnthropicJson(request): AnthropicJson<ClaudeMessagesRequest>| async move {
+                    request.validate_sources().map(|()| request.model)
+                },
+            ),
+        )
+        .layer(DefaultBodyLimit::max(BODY_LIMIT))
+}
+
+async fn send(body: String) -> (StatusCode, Value) {
+    let request = Request::post("/v1/messages")
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap_or_default();
+    let Ok(response) = router().oneshot(request).await;
+    let status = response.status();
+    let bytes = response
+        .into_body()
+        .collect()
+        .await
+        .map(http_body_util::Collected::to_bytes)
+        .unwrap_or_default();
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or(Value::String(String::from_utf8_lossy(&bytes).to_string()));
+    (status, body)
+}
+
+#[rstest]
+#[case(
+    "missing_max_tokens",
+    r#"{"model":"openai/gpt-4.1","messages":[{"role":"user","content":"hi"}]}"#.to_string()
+)]
+#[case(
+    "unknown_content_shape",
+    r#"{"model":"openai/gpt-4.1","max_tokens":10,"messages":[{"role":"user","content":42}]}"#.to_string()
+)]
+#[case(
+    "wrong_field_type",
+    r#"{"model":"openai/gpt-4.1","max_tokens":"ten","messages":[]}"#.to_string()
+)]
+#[case(
+    "tool_missing_input_schema",
+    r#"{"model":"openai/gpt-4.1","max_tokens":10,"messages":[],"tools":[{"name":"get_weather","input_schemma":{}}]}"#.to_string()
+)]
+#[case(
+    "image_source_missing_data",
+    r#"{"model":"openai/gpt-4.1","max_tokens":10,"messages":[{"role":"user","content":[{"type":"image","source":{"type":"base64","media_type":"image/png"}}]}]}"#.to_string()
+)]
+#[case(
+    "image_source_unknown_type",
+    r#"{"model":"openai/gpt-4.1","max_tokens":10,"messages":[{"role":"user","content":[{"type":"text","text":"hi"},{"type":"image","source":{"type":"path","path":"/tmp/cat.png"}}]}]}"#.to_string()
+)]
+#[case(
+    "tool_result_image_source_wrong_type",
+    r#"{"model":"openai/gpt-4.1","max_tokens":10,"messages":[{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_01","content":[{"type":"image","source":{"type":"url","url":42}}]}]}]}"#.to_string()
+)]
+#[case("syntax_error", r#"{"model":"openai/gpt-4.1","#.to_string())]
+#[case(
+    "body_too_large",
+    format!(r#"{{"model":"{}","max_tokens":10,"messages":[]}}"#, "x".repeat(BODY_LIMIT))
+)]
+#[case(
+    "valid_request",
+    r#"{"model":"openai/gpt-4.1","max_tokens":10,"messages":[]}"#.to_string()
+)]
+#[tokio::test]
+async fn verify_malformed_request_rejection(#[case] name: &str, #[case] body: String) {
+    let (status, body) = send(body).await;
+    insta::assert_debug_snapshot!(name, (status.as_u16(), body));
+}
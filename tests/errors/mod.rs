@@ -0,0 +1,3 @@
+mod malformed;
+mod retry;
+mod upstream;
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use crate::{
+    conversion::scenarios::{final_chunk, text_chunk},
+    helpers,
+};
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::stream::{StreamOptions, convert_openai_stream_to_anthropic},
+    directives::models::Settings,
+    models::claude::{AnthropicStreamEvent, ClaudeMessagesRequest, MessageStart},
+    retry::Reconnect,
+};
+use rstest::rstest;
+
+const OVERLOADED_PAYLOAD: &str =
+    r#"data: {"error":{"message":"Provider is overloaded","code":503}}"#;
+
+fn mock_response_from_sse(sse_data: String) -> reqwest::Response {
+    let response = http::Response::builder()
+        .status(200)
+        .header("content-type", "text/event-stream")
+        .body(reqwest::Body::from(sse_data))
+        .unwrap_or_default();
+    reqwest::Response::from(response)
+}
+
+fn chunk_line(chunk: &ant_compat::models::openai::OpenAIStreamChunk) -> String {
+    format!(
+        "data: {}\n\n",
+        serde_json::to_string(chunk).unwrap_or_default()
+    )
+}
+
+fn reconnect_with_text() -> Reconnect {
+    Arc::new(|| {
+        Box::pin(async {
+            Ok(mock_response_from_sse(
+                chunk_line(&text_chunk("Recovered")) + &chunk_line(&final_chunk("stop")),
+            ))
+        })
+    })
+}
+
+#[rstest]
+#[case("retry_before_content", String::new())]
+#[case("no_retry_after_content", chunk_line(&text_chunk("Partial")))]
+#[tokio::test]
+async fn verify_stream_retry(#[case] name: &str, #[case] prefix: String) {
+    let model = "test-model";
+    let request = ClaudeMessagesRequest {
+        model: model.to_string(),
+        messages: vec![],
+        max_tokens: 1024,
+        stream: Some(true),
+        system: None,
+        stop_sequences: None,
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+        service_tier: None,
+    };
+    let adapter = RequestAdapter::for_model(model, &Settings::default(), &Config::default());
+    let mut mock_state = helpers::mock_app_state();
+    mock_state.config = Arc::new(Config {
+        upstream_max_retries: 1,
+        upstream_retry_base_delay_ms: 1,
+        upstream_retry_max_delay_ms: 1,
+        ..Config::default()
+    });
+    let initial = mock_response_from_sse(format!("{prefix}{OVERLOADED_PAYLOAD}\n\n"));
+
+    let anthropic_stream = convert_openai_stream_to_anthropic(
+        initial,
+        model,
+        &adapter,
+        &request,
+        &mock_state,
+        StreamOptions {
+            reconnect: Some(reconnect_with_text()),
+            ..StreamOptions::default()
+        },
+    );
+
+    let mut output_events = helpers::collect_and_parse_stream(anthropic_stream).await;
+    for event in &mut output_events {
+        if let AnthropicStreamEvent::MessageStart(MessageStart { message, .. }) = event {
+            message.id = "[redacted-id]".to_string();
+        }
+    }
+
+    insta::assert_debug_snapshot!(name, output_events);
+}
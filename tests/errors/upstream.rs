@@ -0,0 +1,52 @@
+use ant_compat::error::UpstreamFailure;
+use http::StatusCode;
+use rstest::rstest;
+use serde_json::json;
+
+#[rstest]
+#[case(
+    "openai_rate_limit",
+    429,
+    r#"{"error":{"message":"Rate limit reached for requests","type":"rate_limit_exceeded","code":"rate_limit_exceeded"}}"#
+)]
+#[case(
+    "openai_invalid_request",
+    400,
+    r#"{"error":{"message":"This model's maximum context length is 128000 tokens","type":"invalid_request_error","code":"context_length_exceeded"}}"#
+)]
+#[case("string_error", 401, r#"{"error":"Invalid API key"}"#)]
+#[case("plain_text_overloaded", 503, "upstream connect error")]
+#[case(
+    "hint_only",
+    500,
+    r#"{"error":{"message":"Model is overloaded","type":"server_overloaded"}}"#
+)]
+#[case("detail_not_found", 404, r#"{"detail":"Model not found"}"#)]
+fn verify_upstream_error_classification(
+    #[case] name: &str,
+    #[case] status: u16,
+    #[case] body: &str,
+) {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let failure = UpstreamFailure::new(status, body, None);
+    insta::assert_debug_snapshot!(
+        name,
+        (
+            failure.error_type(),
+            failure.response_status().as_u16(),
+            failure.message
+        )
+    );
+}
+
+#[rstest]
+fn verify_mid_stream_error_classification() {
+    let payload = json!({
+        "error": {
+            "message": "Provider returned error",
+            "code": 429
+        }
+    });
+    let failure = UpstreamFailure::from_stream_payload(&payload);
+    insta::assert_debug_snapshot!(failure.map(|f| (f.error_type(), f.message)));
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use ant_compat::{
+    config::{Config, ReadinessProbe},
+    health::{liveness, readiness},
+    state::AppState,
+};
+use axum::{Router, http::StatusCode, routing::get};
+use rstest::rstest;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+
+use crate::helpers;
+
+// answers /models with 401, everything else falls through to axum's 404/405
+async fn spawn_upstream() -> String {
+    let Ok(listener) = TcpListener::bind("127.0.0.1:0").await else {
+        return "http://127.0.0.1:9".to_string();
+    };
+    let address = listener
+        .local_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_default();
+    let app = Router::new().route("/v1/models", get(|| async { StatusCode::UNAUTHORIZED }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    format!("http://{address}/v1")
+}
+
+fn state_with(openai_base_url: String, readiness_probe: ReadinessProbe) -> AppState {
+    let mut state = helpers::mock_app_state();
+    state.config = Arc::new(Config {
+        openai_base_url,
+        readiness_probe,
+        readiness_probe_key: Some("sk-probe".to_string()),
+        ..Config::default()
+    });
+    state
+}
+
+fn redact(mut body: Value) -> Value {
+    body["uptime_secs"] = json!("[uptime]");
+    body["version"] = json!("[version]");
+    if let Some(upstream) = body.get_mut("upstream") {
+        upstream["checked_secs_ago"] = json!("[age]");
+        if !upstream["error"].is_null() {
+            upstream["error"] = json!("[error]");
+        }
+    }
+    body
+}
+
+#[rstest]
+#[case("probe_off", false, ReadinessProbe::Off)]
+#[case("probe_head_reachable", false, ReadinessProbe::Head)]
+#[case("probe_head_unreachable", true, ReadinessProbe::Head)]
+#[case("probe_models_rejected", false, ReadinessProbe::Models)]
+#[tokio::test]
+async fn verify_readiness(
+    #[case] name: &str,
+    #[case] unreachable: bool,
+    #[case] probe: ReadinessProbe,
+) {
+    let base_url = if unreachable {
+        "http://127.0.0.1:9/v1".to_string()
+    } else {
+        spawn_upstream().await
+    };
+    let state = state_with(base_url, probe);
+    let (ready, body) = readiness(&state).await;
+    insta::assert_debug_snapshot!(name, (ready, redact(body)));
+}
+
+#[rstest]
+fn verify_liveness() {
+    let state = helpers::mock_app_state();
+    insta::assert_debug_snapshot!(redact(liveness(&state)));
+}
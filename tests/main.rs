@@ -1,13 +1,23 @@
 mod adapters;
+mod auth;
+mod config;
 mod conversion;
 mod directives;
+mod errors;
+mod health;
+mod shutdown;
+mod telemetry;
 
 pub mod helpers {
-    use ant_compat::state::AppState;
+    use ant_compat::{
+        config::Config, conversion::images::ImageFetcher, health::UpstreamProbe,
+        shutdown::StreamTracker, state::AppState,
+    };
     use reqwest::Client;
     use saphyr::{LoadableYamlNode, ScalarOwned, YamlOwned};
-    use std::{fs, path::Path};
+    use std::{fs, path::Path, sync::Arc, time::Instant};
 
+    #[must_use]
     pub fn load_system_prompt_fixture() -> String {
         let path = Path::new("tests/fixtures/system_prompt.yaml");
         fs::read_to_string(path)
@@ -31,12 +41,26 @@ pub mod helpers {
             .unwrap_or_default()
     }
 
+    // building only fails without a tls backend, where Client::new below panics the same way
+    #[expect(clippy::expect_used)]
+    fn mock_image_fetcher() -> ImageFetcher {
+        ImageFetcher::new(&Config::default()).expect("the image client should build")
+    }
+
+    #[must_use]
     pub fn mock_app_state() -> AppState {
         AppState {
-            openai_base_url: "http://localhost:8080".to_string(),
-            default_haiku_model: "test-model".to_string(),
+            config: Arc::new(Config {
+                openai_base_url: "http://localhost:8080".to_string(),
+                haiku_model: "test-model".to_string(),
+                ..Config::default()
+            }),
             http_client: Client::new(),
-            idle_connection_timeout: 60,
+            image_fetcher: mock_image_fetcher(),
+            streams: StreamTracker::default(),
+            upstream_probe: Arc::new(UpstreamProbe::default()),
+            started_at: Instant::now(),
+            metrics: None,
         }
     }
 
@@ -47,23 +71,22 @@ pub mod helpers {
     use bytes::Bytes;
     use futures_util::stream::{Stream, StreamExt};
 
-    pub async fn mock_response_from_chunks(chunks: Vec<OpenAIStreamChunk>) -> reqwest::Response {
-        let sse_data: Vec<u8> = chunks
-            .into_iter()
-            .map(|chunk| {
-                let json = serde_json::to_string(&chunk)
-                    .expect("Serialization of a test data struct should not fail");
-                format!("data: {json}\n\n")
-            })
-            .collect::<String>()
-            .into_bytes();
+    #[must_use]
+    pub fn mock_response_from_chunks(chunks: Vec<OpenAIStreamChunk>) -> reqwest::Response {
+        let mut sse_data = String::new();
+        for chunk in chunks {
+            // test chunks are plain data structs, so serialization cannot fail
+            let json = serde_json::to_string(&chunk).unwrap_or_default();
+            sse_data.push_str("data: ");
+            sse_data.push_str(&json);
+            sse_data.push_str("\n\n");
+        }
 
-        let body = reqwest::Body::from(sse_data);
-        let response = http::Response::builder()
-            .status(200)
-            .header("content-type", "text/event-stream")
-            .body(body)
-            .expect("Building a static HTTP response should not fail");
+        let mut response = http::Response::new(reqwest::Body::from(sse_data));
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("text/event-stream"),
+        );
 
         reqwest::Response::from(response)
     }
@@ -75,14 +98,16 @@ pub mod helpers {
         let mut stream = Box::pin(stream);
 
         while let Some(item) = stream.next().await {
-            let bytes = item.expect("Test stream should not produce I/O errors");
+            // an error ends the stream, leaving the events seen so far to be asserted on
+            let Ok(bytes) = item else {
+                break;
+            };
             let lines = String::from_utf8_lossy(&bytes);
             for line in lines.split('\n') {
-                if line.starts_with("data: ") {
-                    let json_str = &line[6..];
-                    if let Ok(event) = serde_json::from_str(json_str) {
-                        events.push(event);
-                    }
+                if let Some(json_str) = line.strip_prefix("data: ")
+                    && let Ok(event) = serde_json::from_str(json_str)
+                {
+                    events.push(event);
                 }
             }
         }
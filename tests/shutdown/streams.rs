@@ -0,0 +1,87 @@
+use ant_compat::{
+    models::claude::{AnthropicStreamEvent, MessageStop},
+    shutdown::{SseStream, StreamTracker},
+    telemetry::EventStream,
+};
+use bytes::Bytes;
+use futures_util::{StreamExt, stream};
+use rstest::rstest;
+
+fn pending_after_first_event() -> SseStream {
+    Box::pin(
+        stream::once(async { Ok(Bytes::from("event: ping\ndata: {\"type\":\"ping\"}\n\n")) })
+            .chain(stream::pending()),
+    )
+}
+
+fn finished_stream() -> SseStream {
+    Box::pin(stream::iter([Ok(Bytes::from(
+        "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+    ))]))
+}
+
+async fn collect_text(stream: SseStream) -> String {
+    stream
+        .map(|item| item.map(|bytes| String::from_utf8_lossy(&bytes).to_string()))
+        .filter_map(|item| async move { item.ok() })
+        .collect::<String>()
+        .await
+}
+
+#[rstest]
+#[tokio::test]
+async fn verify_stream_closed_at_deadline() {
+    let tracker = StreamTracker::default();
+    let mut stream = tracker.track(pending_after_first_event());
+    let first = stream.next().await.and_then(Result::ok);
+    let active_while_streaming = tracker.active_streams();
+
+    tracker.close_remaining();
+    let remaining = collect_text(stream).await;
+
+    insta::assert_debug_snapshot!((
+        first,
+        active_while_streaming,
+        remaining,
+        tracker.active_streams()
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn verify_finished_stream_untouched() {
+    let tracker = StreamTracker::default();
+    let output = collect_text(tracker.track(finished_stream())).await;
+    tracker.close_remaining();
+    insta::assert_debug_snapshot!((output, tracker.active_streams()));
+}
+
+fn events_pending_after_first() -> EventStream {
+    Box::pin(
+        stream::once(async { Ok(AnthropicStreamEvent::MessageStop(MessageStop {})) })
+            .chain(stream::pending()),
+    )
+}
+
+// an aggregated response has no sse frame to write, it ends with the error instead
+#[rstest]
+#[tokio::test]
+async fn verify_events_closed_at_deadline() {
+    let tracker = StreamTracker::default();
+    let mut events = tracker.track_events(events_pending_after_first());
+    let first = events.next().await.map(|event| event.is_ok());
+    let active_while_streaming = tracker.active_streams();
+
+    tracker.close_remaining();
+    let remaining: Vec<_> = events
+        .map(|event| event.err().map(|error| error.to_error_body()))
+        .collect()
+        .await;
+
+    insta::assert_debug_snapshot!((
+        first,
+        active_while_streaming,
+        remaining,
+        tracker.active_streams()
+    ));
+}
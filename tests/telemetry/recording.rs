@@ -0,0 +1,134 @@
+use std::sync::OnceLock;
+
+use ant_compat::{
+    adapters::RequestAdapter,
+    config::Config,
+    conversion::stream::{StreamOptions, convert_openai_stream_to_anthropic},
+    directives::models::Settings,
+    models::{claude::ClaudeMessagesRequest, openai::OpenAIUsage},
+    telemetry::{RequestMetrics, install_recorder, model_label, tenant_label},
+};
+use futures_util::StreamExt;
+use metrics_exporter_prometheus::PrometheusHandle;
+use rstest::rstest;
+
+use crate::{
+    conversion::scenarios::{final_chunk, text_chunk},
+    helpers,
+};
+
+// the recorder is process global, so every test shares one and filters by its own model label
+fn recorder() -> Option<&'static PrometheusHandle> {
+    static HANDLE: OnceLock<Option<PrometheusHandle>> = OnceLock::new();
+    HANDLE.get_or_init(install_recorder).as_ref()
+}
+
+fn request(model: &str) -> ClaudeMessagesRequest {
+    ClaudeMessagesRequest {
+        model: model.to_string(),
+        messages: vec![],
+        max_tokens: 1024,
+        stream: Some(true),
+        system: None,
+        stop_sequences: None,
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+        service_tier: None,
+    }
+}
+
+// latency buckets and sums depend on timing, only counters and observation counts are stable
+fn rendered_lines(model: &str) -> Vec<String> {
+    let rendered = recorder().map(PrometheusHandle::render).unwrap_or_default();
+    let mut lines: Vec<String> = rendered
+        .lines()
+        .filter(|line| line.contains(&format!("model=\"{model}\"")))
+        .filter(|line| !line.contains("_bucket{") && !line.contains("_sum{"))
+        .map(str::to_string)
+        .collect();
+    lines.sort();
+    lines
+}
+
+#[rstest]
+#[case("completed_stream", None)]
+#[case("client_closed_stream", Some(1))]
+#[tokio::test]
+async fn test_stream_metrics(#[case] name: &str, #[case] read_events: Option<usize>) {
+    recorder();
+    let model = format!("telemetry-{name}");
+    let mut last = final_chunk("stop");
+    last.usage = OpenAIUsage {
+        prompt_tokens: 12,
+        completion_tokens: 7,
+        ..OpenAIUsage::default()
+    };
+    let response = helpers::mock_response_from_chunks(vec![text_chunk("Hello"), last]);
+    let adapter = RequestAdapter::for_model(&model, &Settings::default(), &Config::default());
+    let metrics = RequestMetrics::new(&model, adapter.adapter_set(), true, None);
+
+    let stream = convert_openai_stream_to_anthropic(
+        response,
+        &model,
+        &adapter,
+        &request(&model),
+        &helpers::mock_app_state(),
+        StreamOptions {
+            metrics: Some(metrics),
+            ..StreamOptions::default()
+        },
+    );
+    match read_events {
+        Some(count) => {
+            let _ = stream.take(count).collect::<Vec<_>>().await;
+        }
+        None => {
+            let _ = stream.collect::<Vec<_>>().await;
+        }
+    }
+
+    insta::assert_debug_snapshot!(name, rendered_lines(&model));
+}
+
+#[tokio::test]
+async fn test_request_outcome_labels() {
+    recorder();
+    let model = "telemetry-outcomes";
+    let metrics = RequestMetrics::new(
+        model,
+        "defaults".to_string(),
+        false,
+        Some(tenant_label("sk-tenant")),
+    );
+    metrics.record_upstream_latency();
+    metrics.record_outcome("rate_limit_error");
+
+    insta::assert_debug_snapshot!(rendered_lines(model));
+}
+
+#[rstest]
+#[case("sk-ant-example")]
+#[case("")]
+fn test_tenant_label_is_short_digest(#[case] api_key: &str) {
+    let label = tenant_label(api_key);
+    assert_eq!(label.len(), 12);
+    insta::assert_snapshot!(format!("tenant_label_{}", api_key.len()), label);
+}
+
+#[rstest]
+#[case("openai/gpt-4.1-mini", "openai/gpt-4.1-mini")]
+#[case("openai/gpt-5", "openai/gpt-5")]
+#[case("claude-sonnet-4-5-20250929", "other")]
+#[case("made-up-model-7f3a", "other")]
+fn test_model_label_is_bounded(#[case] model: &str, #[case] expected: &str) {
+    let config = Config {
+        metrics_models: vec!["openai/gpt-5".to_string()],
+        ..Config::default()
+    };
+    assert_eq!(model_label(model, &config), expected);
+}